@@ -1,22 +1,51 @@
 use std::path::{PathBuf, Path};
+use std::collections::HashMap;
 
 pub mod bb_content;
 pub mod bb_announcement;
 pub mod bb_gradebook;
+pub mod bb_calendar;
+pub mod bb_manifest;
+pub mod bb_history;
+pub mod bb_membership;
+pub mod bb_group;
+pub mod bb_discussion;
+pub mod bb_course_info;
 use bb_content::BBContent;
+use bb_content::bb_attachment::BBAttachmentDownload;
 use bb_announcement::BBAnnouncement;
-use bb_gradebook::BBGradebookColumn;
+use bb_announcement::bb_announcement_cache::BBAnnouncementCache;
+use bb_announcement::bb_user::BBUserCache;
+use bb_gradebook::{BBGradebookColumn, BBColumnGrade};
+use bb_gradebook::bb_grade::BBGrade;
+use bb_gradebook::bb_attempt::{BBAttempt, BBAttemptFile};
+use bb_calendar::BBCalendarItem;
+use bb_manifest::{BBManifest, BBManifestFile};
+use bb_history::BBHistory;
+use bb_membership::BBMembership;
+use bb_group::BBGroup;
+use bb_discussion::BBDiscussion;
+use bb_discussion::bb_message::BBMessage;
+use bb_discussion::bb_discussion_cache::BBDiscussionCache;
+use crate::export::discussion_document::{DocumentFormat, ThreadDocument};
+use bb_announcement::bb_user::BBUser;
+use bb_course_info::BBCourseInfo;
 use crate::utils::input_utils::stdin_trimmed_line;
-use crate::utils::time_utils::{partial_cmp_dt, utc_now};
-use crate::bb_session::BBSession;
+use crate::utils::filename_utils::{valid_filename, valid_dir_name};
+use crate::utils::time_utils::{partial_cmp_dt, utc_now, utc_api, local_rfc2822, relative_to_now};
+use crate::utils::fs_utils::path_size;
+use crate::bb_session::{BBSession, BBValidators, BBConditionalDownload};
+use crate::bb_term::BBTerm;
+use crate::content_store::{ContentStore, StoreReport};
 
 pub struct BBCourse {
     pub course_code: String,
     pub semester: String,
     pub alias: String,
     out_dir: PathBuf,
-    id: String,
+    pub id: String,
     pub last_tree_download: String,
+    pub info: BBCourseInfo,
 }
 
 impl BBCourse {
@@ -36,6 +65,7 @@ impl BBCourse {
             out_dir: out_dir.to_path_buf(),
             id: id.to_string(),
             last_tree_download: last_tree_download.to_string(),
+            info: BBCourseInfo::default(),
         }
     }
 
@@ -48,29 +78,49 @@ impl BBCourse {
         }).collect())
     }
 
-    pub fn register(session: &BBSession, out_dir: &Path) -> BBCourse {
+    pub fn register(session: &BBSession, out_dir: &Path, term: Option<&BBTerm>) -> BBCourse {
         println!("Please enter the course code (format: TMA4100):");
         let course_code = stdin_trimmed_line();
         
-        let semester = std::env::var("BBCM_SEMESTER").unwrap_or_else(|_| {
-            println!("Please enter the semester (format: 2020_V, 2021_H):"); // This matches the NTNU courseId convention
-            stdin_trimmed_line()
-        });
+        let (semester, query) = match term {
+            Some(term) => (term.semester().to_string(), format!("courseId={}&termId={}", course_code, term.id)),
+            None => {
+                let semester = std::env::var("BBCM_SEMESTER").unwrap_or_else(|_| {
+                    println!("Please enter the semester (format: 2020_V, 2021_H):"); // This matches the NTNU courseId convention
+                    stdin_trimmed_line()
+                });
+                let query = format!("courseId={}%{}", course_code, semester);
+                (semester, query)
+            },
+        };
 
-        let courses_json = session.download_courses_json(&[&format!("courseId={}%{}", course_code, semester)]).expect("Error: Could not download courses json");
+        let courses_json = session.download_courses_json(&[&query]).expect("Error: Could not download courses json");
         let (id, name) = BBCourse::ids_and_names_from_json_results(courses_json).expect("Error: Could not parse courses json").first().expect("Error: No matching course found").to_owned();
 
         println!("Found course \"{}\".\nPlease enter an alias for the new course:", name);
         let alias = stdin_trimmed_line();
 
-        BBCourse::new(
+        let mut course = BBCourse::new(
             &course_code,
             &semester,
             &alias,
-            &out_dir.join(format!("bbcm_{}\\{}", semester, alias)),
+            &out_dir.join(format!("bbcm_{}\\{}", valid_dir_name(&semester), alias)),
             &id,
             ""
-        )
+        );
+        course.info.name = name;
+        if let Err(err) = course.refresh_info(session) {
+            eprintln!("Error fetching course details: {}", err);
+        }
+        course
+    }
+
+    fn download_course_json(&self, session: &BBSession) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let url = format!("https://{}/learn/api/public/v3/courses/{}",
+            session.domain,
+            self.id);
+
+        session.download_bytes(&url)
     }
         
     fn download_course_contents_json(&self, session: &BBSession, query_parameters: &[&str]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
@@ -115,23 +165,348 @@ impl BBCourse {
         session.download_bytes(&url)
     }
 
-    fn get_course_root_content(&self, session: &BBSession) -> Result<Vec<BBContent>, Box<dyn std::error::Error>> {
+    fn download_course_grade_json(&self, session: &BBSession, column_id: &str, user_id: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let url = format!("https://{}/learn/api/public/v1/courses/{}/gradebook/columns/{}/users/{}",
+            session.domain,
+            self.id,
+            column_id,
+            user_id);
+
+        session.download_bytes(&url)
+    }
+
+    fn download_column_attempts_json(&self, session: &BBSession, column_id: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let url = format!("https://{}/learn/api/public/v1/courses/{}/gradebook/columns/{}/attempts",
+            session.domain,
+            self.id,
+            column_id);
+
+        session.download_bytes(&url)
+    }
+
+    // Files submitted with an attempt, or with "feedback/files", the feedback files attached by the instructor
+    fn attempt_files_url(&self, session: &BBSession, attempt_id: &str, files_path: &str) -> String {
+        format!("https://{}/learn/api/public/v1/courses/{}/gradebook/attempts/{}/{}",
+            session.domain,
+            self.id,
+            attempt_id,
+            files_path)
+    }
+
+    fn download_course_calendar_items_json(&self, session: &BBSession, query_parameters: &[&str]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut url = format!("https://{}/learn/api/public/v1/calendars/items?courseId={}",
+            session.domain,
+            self.id);
+
+        if !query_parameters.is_empty() {
+            url.extend(format!("&{}", query_parameters.join("&")).chars());
+        }
+
+        session.download_bytes(&url)
+    }
+
+    fn download_course_groups_json(&self, session: &BBSession) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let url = format!("https://{}/learn/api/public/v1/courses/{}/groups",
+            session.domain,
+            self.id);
+
+        session.download_bytes(&url)
+    }
+
+    fn download_group_members_json(&self, session: &BBSession, group_id: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let url = format!("https://{}/learn/api/public/v1/courses/{}/groups/{}/users",
+            session.domain,
+            self.id,
+            group_id);
+
+        session.download_bytes(&url)
+    }
+
+    fn download_group_contents_json(&self, session: &BBSession, group_id: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let url = format!("https://{}/learn/api/public/v1/courses/{}/groups/{}/contents",
+            session.domain,
+            self.id,
+            group_id);
+
+        session.download_bytes(&url)
+    }
+
+    fn download_content_json(&self, session: &BBSession, content_id: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let url = format!("https://{}/learn/api/public/v1/courses/{}/contents/{}?{}",
+            session.domain,
+            self.id,
+            content_id,
+            BBContent::DEFAULT_FIELDS);
+
+        session.download_bytes(&url)
+    }
+
+    fn download_course_discussions_json(&self, session: &BBSession) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let url = format!("https://{}/learn/api/public/v1/courses/{}/discussions",
+            session.domain,
+            self.id);
+
+        session.download_bytes(&url)
+    }
+
+    fn download_discussion_messages_json(&self, session: &BBSession, discussion_id: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let url = format!("https://{}/learn/api/public/v1/courses/{}/discussions/{}/messages",
+            session.domain,
+            self.id,
+            discussion_id);
+
+        session.download_bytes(&url)
+    }
+
+    fn download_message_replies_json(&self, session: &BBSession, discussion_id: &str, message_id: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let url = format!("https://{}/learn/api/public/v1/courses/{}/discussions/{}/messages/{}/replies",
+            session.domain,
+            self.id,
+            discussion_id,
+            message_id);
+
+        session.download_bytes(&url)
+    }
+
+    fn get_course_root_content(&self, session: &BBSession) -> Result<Vec<BBContent<'_>>, Box<dyn std::error::Error>> {
         let json = self.download_course_root_contents_json(session)?;
         BBContent::vec_from_json_results(json, self)
     }
 
+    pub fn out_dir(&self) -> &Path {
+        &self.out_dir
+    }
+
+    pub fn load_manifest(&self) -> Result<BBManifest, Box<dyn std::error::Error>> {
+        BBManifest::load(&self.out_dir)
+    }
+
+    pub fn load_history(&self) -> Result<BBHistory, Box<dyn std::error::Error>> {
+        BBHistory::load(&self.out_dir)
+    }
+
+    // Moves an earlier version of a replaced file into the course history
+    fn keep_version(history: &mut BBHistory, path: &Path, previous_path: &Path) {
+        match history.record(path, previous_path) {
+            Ok(version) => eprintln!("Kept the earlier version of {:?} as v{}.", path.file_name().unwrap_or_default(), version.number),
+            Err(err) => eprintln!("Error keeping the earlier version of {:?}: {}", path, err),
+        }
+    }
+
+    // Returns the download size, and the size of unchanged attachments that weren't downloaded again.
+    // Downloaded attachments are added to the content store, if it is enabled.
     pub fn download_course_content_tree(
         &self, 
         session: &BBSession, 
+        store: Option<&ContentStore>,
         overwrite: bool
-    ) -> Result<f64, Box<dyn std::error::Error>> {
+    ) -> Result<(f64, f64), Box<dyn std::error::Error>> {
         let mut threads = Vec::new();
+        let mut manifest = self.load_manifest()?;
+        let mut history = self.load_history()?;
+        let previously_synced: HashMap<PathBuf, String> = manifest.files.iter().map(|(path, file)| (path.clone(), file.synced.clone())).collect();
         // std::fs::create_dir_all(&self.tree_dir).expect("Error creating tree dir"); //Hvorfor klagde ikke denne når jeg hadde "?"?
         for content in self.get_course_root_content(session)? {
-            content.download_children(session, &self.out_dir, overwrite, &mut threads)?;
+            content.download_children(session, &self.out_dir, overwrite, &mut threads, &mut manifest)?;
         }
-        let total_download_size = threads.into_iter().map(|t| t.join().expect("Failed to join thread")).sum();
-        Ok(total_download_size)
+        self.download_group_content_trees(session, overwrite, &mut threads, &mut manifest)?;
+        let mut total_download_size = 0.0;
+        let mut total_saved_size = 0.0;
+        let mut store_report = StoreReport::default();
+        let mut store_errors = Vec::new();
+        for download in threads.into_iter().map(|t| t.join().expect("Failed to join thread")) {
+            total_download_size += download.size;
+            total_saved_size += download.saved;
+            if let Some(previous_path) = &download.replaced {
+                BBCourse::keep_version(&mut history, &download.path, previous_path);
+            }
+            let path = manifest.relative_path(&download.path);
+            if let Some(file) = manifest.files.get_mut(&path) {
+                if let Some(validators) = download.validators {
+                    file.validators = validators;
+                    if let Some(store) = store {
+                        match store.add(&download.path) {
+                            Ok(report) => store_report.merge(report),
+                            Err(err) => store_errors.push(err),
+                        }
+                    }
+                }
+                // Kept files, and files downloaded again with the same content, count as synced when their content last changed
+                if !download.changed {
+                    if let Some(synced) = previously_synced.get(&path) {
+                        file.synced = synced.clone();
+                    }
+                }
+            }
+        }
+        if store_report.duplicates > 0 {
+            eprintln!("Linked {} duplicate files from the content store ({:.1} MB).", store_report.duplicates, store_report.reclaimed as f64/1000000.0);
+        }
+        if let Some(err) = store_errors.first() {
+            eprintln!("Error adding {} files to the content store: {}", store_errors.len(), err);
+        }
+        manifest.prune(&[BBManifestFile::ATTACHMENT, BBManifestFile::LINK], true);
+        manifest.refresh_sizes();
+        manifest.save()?;
+        history.save()?;
+        Ok((total_download_size, total_saved_size))
+    }
+
+    // Groups
+    pub fn get_course_groups(&self, session: &BBSession) -> Result<Vec<BBGroup>, Box<dyn std::error::Error>> {
+        let json = self.download_course_groups_json(session)?;
+        BBGroup::vec_from_json_results(json)
+    }
+
+    pub fn get_group_member_ids(&self, session: &BBSession, group_id: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let json = self.download_group_members_json(session, group_id)?;
+        BBGroup::member_ids_from_json_results(json)
+    }
+
+    pub fn get_my_groups(&self, session: &BBSession, user_id: &str) -> Result<Vec<BBGroup>, Box<dyn std::error::Error>> {
+        Ok(self.get_course_groups(session)?.into_iter().filter(|group| {
+            self.get_group_member_ids(session, &group.id)
+                .map(|member_ids| member_ids.iter().any(|id| id == user_id))
+                .unwrap_or(false)
+        }).collect())
+    }
+
+    // Results are either content items or content group associations, depending on the Learn version
+    fn get_group_contents(&self, session: &BBSession, group_id: &str) -> Result<Vec<BBContent<'_>>, Box<dyn std::error::Error>> {
+        let json_string = String::from_utf8(self.download_group_contents_json(session, group_id)?)?;
+        let parsed_json = json::parse(&json_string)?;
+        let mut contents = Vec::new();
+        for member in parsed_json["results"].members() {
+            let content_id = if member["contentId"].is_null() { &member["id"] } else { &member["contentId"] };
+            contents.push(BBContent::from_json(self.download_content_json(session, &content_id.to_string())?, self)?);
+        }
+        Ok(contents)
+    }
+
+    // Content shared with my groups is placed under groups/<group name>/
+    fn download_group_content_trees(
+        &self,
+        session: &BBSession,
+        overwrite: bool,
+        threads: &mut Vec<std::thread::JoinHandle<BBAttachmentDownload>>,
+        manifest: &mut BBManifest,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let groups = match BBUser::get_current(session).and_then(|me| self.get_my_groups(session, &me.id)) {
+            Ok(groups) => groups,
+            Err(_) => {
+                // Courses without groups may refuse the request
+                manifest.keep_below(&self.out_dir.join("groups"));
+                return Ok(());
+            },
+        };
+        for group in groups {
+            let group_path = self.out_dir.join("groups").join(valid_dir_name(&group.name));
+            match self.get_group_contents(session, &group.id) {
+                Ok(contents) => {
+                    std::fs::create_dir_all(&group_path)?;
+                    for content in contents {
+                        content.download_children(session, &group_path, overwrite, threads, manifest)?;
+                    }
+                },
+                Err(err) => {
+                    eprintln!("Error downloading content for group \"{}\": {}", group.name, err);
+                    manifest.keep_below(&group_path);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Discussions
+    fn get_message_replies(&self, session: &BBSession, discussion_id: &str, message_id: &str) -> Result<Vec<BBMessage>, Box<dyn std::error::Error>> {
+        let json = self.download_message_replies_json(session, discussion_id, message_id)?;
+        let mut replies = BBMessage::vec_from_json_results(json, discussion_id)?;
+        for i in 0..replies.len() {
+            let nested_replies = self.get_message_replies(session, discussion_id, &replies[i].id)?;
+            replies.extend(nested_replies);
+        }
+        Ok(replies)
+    }
+
+    // Returns the number of threads that were new or changed since the last sync.
+    // Threads whose reply count and "modified" are unchanged are skipped. Learn versions that don't report reply counts
+    // give no sign of new replies at the thread level, and the public API has no way to list the replies of a whole thread,
+    // so then the replies of every message are fetched, one request each. Unchanged responses are revalidated by the
+    // response cache, so they aren't downloaded again.
+    pub fn sync_discussions(&self, session: &BBSession, work_dir: &Path, users: &mut BBUserCache, format: DocumentFormat) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut cache = BBDiscussionCache::load(&work_dir.join("discussions").join(format!("{}.json", self.id)))?;
+        let mut manifest = self.load_manifest()?;
+        let mut updated_threads = 0;
+        for discussion in BBDiscussion::vec_from_json_results(self.download_course_discussions_json(session)?)? {
+            let discussion_dir = self.out_dir.join("discussions").join(valid_dir_name(&discussion.title));
+            let threads = match self.download_discussion_messages_json(session, &discussion.id).and_then(|json| BBMessage::vec_from_json_results(json, &discussion.id)) {
+                Ok(threads) => threads,
+                Err(err) => {
+                    eprintln!("Error downloading threads of \"{}\": {}", discussion.title, err);
+                    manifest.keep_content_files(&discussion.id);
+                    continue;
+                },
+            };
+            for thread in threads {
+                // The id keeps threads with the same (or no) title apart
+                let title = if thread.title.trim().is_empty() { "Untitled" } else { &thread.title };
+                let thread_path = discussion_dir.join(valid_filename(&format!("{} ({}).{}", title, thread.id, format.extension())));
+                if cache.is_thread_unchanged(&thread) && thread_path.exists() {
+                    manifest.keep_file(&thread_path);
+                    continue;
+                }
+                let replies = match self.get_message_replies(session, &discussion.id, &thread.id) {
+                    Ok(replies) => replies,
+                    Err(err) => {
+                        eprintln!("Error downloading replies to \"{}\" in \"{}\": {}", title, discussion.title, err);
+                        manifest.keep_source_files(&thread.id);
+                        continue;
+                    },
+                };
+                // Updated either way, so that the reply count of the thread is cached
+                let up_to_date = cache.is_up_to_date(&thread, &replies);
+                cache.update_thread(thread.clone(), replies);
+                if up_to_date && thread_path.exists() {
+                    manifest.keep_file(&thread_path);
+                    continue;
+                }
+                let document = ThreadDocument {
+                    discussion_title: &discussion.title,
+                    thread: &thread,
+                    replies: cache.replies(&thread.id),
+                };
+                users.resolve(session, &document.replies.iter().map(|reply| reply.creator.as_str()).chain(std::iter::once(thread.creator.as_str())).collect::<Vec<&str>>());
+                std::fs::create_dir_all(&discussion_dir)?;
+                document.write(users, format, &thread_path)?;
+                // Documents of the same thread under an earlier title are replaced
+                let earlier_paths: Vec<PathBuf> = manifest.files.values()
+                    .filter(|file| file.kind == BBManifestFile::DISCUSSION && file.source_id == thread.id)
+                    .filter(|file| file.path.extension() == thread_path.extension() && manifest.absolute_path(&file.path) != thread_path)
+                    .map(|file| file.path.clone())
+                    .collect();
+                for earlier_path in earlier_paths {
+                    let _ = std::fs::remove_file(manifest.absolute_path(&earlier_path));
+                    manifest.files.remove(&earlier_path);
+                }
+                manifest.record_file(BBManifestFile {
+                    path: thread_path,
+                    content_id: discussion.id.clone(),
+                    source_id: thread.id.clone(),
+                    kind: String::from(BBManifestFile::DISCUSSION),
+                    mimetype: String::from(if format == DocumentFormat::Html { "text/html" } else { "text/markdown" }),
+                    modified: thread.modified.clone(),
+                    size: 0,
+                    synced: utc_now(),
+                    validators: BBValidators::default(),
+                });
+                updated_threads += 1;
+            }
+        }
+        cache.save()?;
+        manifest.prune(&[BBManifestFile::DISCUSSION], false);
+        manifest.refresh_sizes();
+        manifest.save()?;
+        Ok(updated_threads)
     }
 
     //Announcements
@@ -151,20 +526,62 @@ impl BBCourse {
         BBAnnouncement::vec_from_json_results(json)
     }
     
-    pub fn view_course_announcements(&self, session: &BBSession, limit: Option<usize>, offset: Option<usize>) -> Result<(), Box<dyn std::error::Error>> {
+    fn announcement_cache_path(&self, work_dir: &Path) -> PathBuf {
+        work_dir.join("announcements").join(format!("{}.json", self.id))
+    }
+
+    pub fn load_announcement_cache(&self, work_dir: &Path) -> Result<BBAnnouncementCache, Box<dyn std::error::Error>> {
+        BBAnnouncementCache::load(&self.announcement_cache_path(work_dir))
+    }
+
+    pub fn update_announcement_cache(&self, session: &BBSession, work_dir: &Path) -> Result<BBAnnouncementCache, Box<dyn std::error::Error>> {
+        let mut cache = self.load_announcement_cache(work_dir)?;
+        cache.update(self.get_course_announcements(session, None, None)?);
+        cache.save()?;
+        Ok(cache)
+    }
+    
+    // Returns the shown announcements that are new, for the announcement hooks
+    pub fn view_course_announcements(&self, session: &BBSession, work_dir: &Path, users: &mut BBUserCache, limit: Option<usize>, offset: Option<usize>) -> Result<Vec<BBAnnouncement>, Box<dyn std::error::Error>> {
         let announcements = self.get_course_announcements(session, limit, offset)?;
+        let mut cache = self.load_announcement_cache(work_dir)?;
+        cache.update(announcements.clone());
         if announcements.is_empty() {
             println!("No announcements found.")
         } else {
+            BBAnnouncement::resolve_creators(&announcements, session, users);
             for announcement in announcements.iter().rev() {
-                announcement.view_with_name(session);
+                announcement.view_with_name(users);
             }
         }
-        Ok(())
+        let new_announcements = cache.mark_shown(announcements.iter().map(|announcement| &announcement.id));
+        cache.save()?;
+        Ok(new_announcements)
+    }
+
+    // Returns the number of unread announcements shown, and the ones among them that are new, for the announcement hooks
+    pub fn view_new_course_announcements(&self, session: &BBSession, work_dir: &Path, users: &mut BBUserCache) -> Result<(usize, Vec<BBAnnouncement>), Box<dyn std::error::Error>> {
+        let mut cache = self.update_announcement_cache(session, work_dir)?;
+        let unread: Vec<BBAnnouncement> = cache.unread().into_iter().cloned().collect();
+        BBAnnouncement::resolve_creators(&unread, session, users);
+        for announcement in &unread {
+            announcement.view_with_name(users);
+        }
+        let new_announcements = cache.mark_shown(unread.iter().map(|announcement| &announcement.id));
+        cache.save()?;
+        Ok((unread.len(), new_announcements))
+    }
+
+    // Marks the cached announcements that have been shown as read, without fetching new ones
+    pub fn mark_announcements_read(&self, work_dir: &Path) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut cache = self.load_announcement_cache(work_dir)?;
+        let marked = cache.mark_read();
+        cache.save()?;
+        Ok(marked)
     }
 
     // Gradebook
-    fn get_course_gradebook(&self, session: &BBSession) -> Result<Vec<BBGradebookColumn>, Box<dyn std::error::Error>> {
+    pub fn get_course_gradebook(&self, session: &BBSession) -> Result<Vec<BBGradebookColumn>, Box<dyn std::error::Error>> {
         let json = self.download_course_gradebook_json(session, &[])?;
         BBGradebookColumn::vec_from_json_results(json)
     }
@@ -191,8 +608,331 @@ impl BBCourse {
         Ok(())
     }
 
+    // Calendar
+    pub fn get_course_calendar_items(&self, session: &BBSession, since: &str, until: &str) -> Result<Vec<BBCalendarItem>, Box<dyn std::error::Error>> {
+        let mut query_parameters = Vec::new();
+        if let Some(since) = utc_api(since) {
+            query_parameters.push(format!("since={}", since));
+        }
+        if let Some(until) = utc_api(until) {
+            query_parameters.push(format!("until={}", until));
+        }
+
+        let borrowed_query_parameters: Vec<&str> = query_parameters.iter().map(|s| s.as_str()).collect();
+
+        let json = self.download_course_calendar_items_json(session, &borrowed_query_parameters[..])?;
+        BBCalendarItem::vec_from_json_results(json)
+    }
+
+    // Memberships
+    // Only members with the given course role, if any, are fetched
+    pub fn get_course_memberships(&self, session: &BBSession, role: Option<&str>) -> Result<Vec<BBMembership>, Box<dyn std::error::Error>> {
+        let mut memberships = Vec::new();
+        let mut url = format!("https://{}/learn/api/public/v1/courses/{}/users?expand=user",
+            session.domain,
+            self.id);
+        if let Some(role) = role {
+            url.push_str(&format!("&role={}", role));
+        }
+        loop {
+            let (page, next_page) = BBMembership::vec_from_json_results(session.download_bytes(&url)?)?;
+            memberships.extend(page);
+            match next_page {
+                Some(next_page) => url = format!("https://{}{}", session.domain, next_page),
+                None => break,
+            }
+        }
+        Ok(memberships)
+    }
+
+    // Course details
+    pub fn refresh_info(&mut self, session: &BBSession) -> Result<(), Box<dyn std::error::Error>> {
+        let mut info = BBCourseInfo::from_json(self.download_course_json(session)?)?;
+        // Students may not be allowed to list course members, which shouldn't keep the other details from being refreshed
+        info.instructors = match self.get_course_memberships(session, Some("Instructor")) {
+            Ok(memberships) => memberships.into_iter()
+                .filter(|membership| membership.role == "Instructor")
+                .map(|membership| membership.user.name)
+                .collect(),
+            Err(err) => {
+                eprintln!("Note: Could not look up the instructors of {}: {}", self.alias, err);
+                Vec::new()
+            },
+        };
+        if info.url.is_empty() {
+            info.url = self.web_url(session);
+        }
+        info.refreshed = utc_now();
+        self.info = info;
+        Ok(())
+    }
+
+    // The earliest due date that hasn't passed yet
+    pub fn get_next_deadline(&self, session: &BBSession) -> Result<Option<BBGradebookColumn>, Box<dyn std::error::Error>> {
+        let now = utc_now();
+        let mut upcoming: Vec<BBGradebookColumn> = self.get_course_gradebook(session)?.into_iter()
+            .filter(|gbc| partial_cmp_dt(&gbc.due, &now) == Some(std::cmp::Ordering::Greater))
+            .collect();
+        upcoming.sort_by(|gbc1, gbc2| partial_cmp_dt(&gbc1.due, &gbc2.due).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(upcoming.into_iter().next())
+    }
+
+    pub fn web_url(&self, session: &BBSession) -> String {
+        format!("https://{}/ultra/courses/{}/outline", session.domain, self.id)
+    }
+
+    pub fn get_column_attempts(&self, session: &BBSession, column_id: &str) -> Result<Vec<BBAttempt>, Box<dyn std::error::Error>> {
+        let json = self.download_column_attempts_json(session, column_id)?;
+        BBAttempt::vec_from_json_results(json)
+    }
+
+    fn get_attempt_files(&self, session: &BBSession, files_url: &str) -> Result<Vec<BBAttemptFile>, Box<dyn std::error::Error>> {
+        let json = session.download_bytes(files_url)?;
+        BBAttemptFile::vec_from_json_results(json)
+    }
+
+    // Submissions are placed in a submissions folder next to the assignment's content directory, if the tree has been synced
+    fn submissions_dir(&self, manifest: &BBManifest, gbc: &BBGradebookColumn) -> PathBuf {
+        manifest.content_dir(&gbc.content_id)
+            .and_then(|content_dir| Some(content_dir.parent()?.join("submissions").join(content_dir.file_name()?)))
+            .unwrap_or_else(|| self.out_dir.join("submissions").join(valid_dir_name(&gbc.name)))
+    }
+
+    fn save_attempt_text(manifest: &mut BBManifest, path: &Path, html: &str, content_id: &str, attempt: &BBAttempt, kind: &str) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(path, html)?;
+        manifest.record_file(BBManifestFile {
+            path: path.to_path_buf(),
+            content_id: content_id.to_string(),
+            source_id: attempt.id.clone(),
+            kind: kind.to_string(),
+            mimetype: String::from("text/html"),
+            modified: attempt.modified.clone(),
+            size: 0,
+            synced: utc_now(),
+            validators: BBValidators::default(),
+        });
+        Ok(())
+    }
+
+    // Downloads the files listed at files_url into dir, and returns the download size
+    #[allow(clippy::too_many_arguments)]
+    fn download_attempt_files(
+        &self,
+        session: &BBSession,
+        manifest: &mut BBManifest,
+        history: &mut BBHistory,
+        files_url: &str,
+        dir: &Path,
+        content_id: &str,
+        attempt: &BBAttempt,
+        kind: &str,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        let mut download_size = 0.0;
+        for attempt_file in self.get_attempt_files(session, files_url)? {
+            let file_path = dir.join(valid_filename(&attempt_file.name));
+            let up_to_date = manifest.files.get(&manifest.relative_path(&file_path))
+                .map(|file| file.modified == attempt.modified && file_path.exists())
+                .unwrap_or(false);
+            if up_to_date {
+                manifest.keep_file(&file_path);
+                continue;
+            }
+            std::fs::create_dir_all(dir)?;
+            let url = format!("{}/{}/download", files_url, attempt_file.id);
+            println!("Downloading {:?}", file_path.file_name().unwrap());
+            let previous_path = BBHistory::set_aside(&file_path);
+            match session.download_file_if_changed(&url, &file_path, None) {
+                Ok(BBConditionalDownload::Downloaded { size, .. }) => download_size += size,
+                Ok(BBConditionalDownload::Unchanged) => {},
+                Err(err) => {
+                    if let Some(previous_path) = &previous_path {
+                        BBHistory::discard(previous_path);
+                    }
+                    return Err(err);
+                },
+            }
+            if let Some(previous_path) = previous_path.and_then(|previous_path| BBHistory::changed(previous_path, &file_path)) {
+                BBCourse::keep_version(history, &file_path, &previous_path);
+            }
+            manifest.record_file(BBManifestFile {
+                path: file_path,
+                content_id: content_id.to_string(),
+                source_id: attempt_file.id.clone(),
+                kind: kind.to_string(),
+                mimetype: String::new(),
+                modified: attempt.modified.clone(),
+                size: 0,
+                synced: utc_now(),
+                validators: BBValidators::default(),
+            });
+        }
+        Ok(download_size)
+    }
+
+    pub fn download_course_submissions(&self, session: &BBSession, list_only: bool) -> Result<f64, Box<dyn std::error::Error>> {
+        let mut manifest = self.load_manifest()?;
+        let mut history = self.load_history()?;
+        let mut total_download_size = 0.0;
+        for gbc in self.get_course_gradebook(session)? {
+            if gbc.content_id == "null" {
+                continue; // Only columns linked to an assignment can have attempts
+            }
+            let submissions_dir = self.submissions_dir(&manifest, &gbc);
+            let mut attempts = match self.get_column_attempts(session, &gbc.id) {
+                Ok(attempts) => attempts,
+                Err(_) => {
+                    manifest.keep_below(&submissions_dir);
+                    continue;
+                },
+            };
+            attempts.sort_by(|a1, a2| partial_cmp_dt(&a1.created, &a2.created).unwrap_or(std::cmp::Ordering::Equal));
+            for (i, attempt) in attempts.iter().enumerate() {
+                attempt.view(&gbc.name, i+1);
+                if list_only {
+                    continue;
+                }
+                let attempt_dir = submissions_dir.join(format!("attempt_{}", i+1));
+                std::fs::create_dir_all(&attempt_dir)?;
+                // A failing attempt keeps its earlier files, and doesn't stop the other attempts from syncing
+                for (files_path, dir, kind) in &[
+                    ("files", attempt_dir.clone(), BBManifestFile::SUBMISSION),
+                    ("feedback/files", attempt_dir.join("feedback"), BBManifestFile::FEEDBACK),
+                ] {
+                    match self.download_attempt_files(session, &mut manifest, &mut history, &self.attempt_files_url(session, &attempt.id, files_path), dir, &gbc.content_id, attempt, kind) {
+                        Ok(size) => total_download_size += size,
+                        Err(err) => {
+                            eprintln!("Error downloading {} of attempt {} of {}: {}", if *kind == BBManifestFile::FEEDBACK { "feedback files" } else { "files" }, i+1, gbc.name, err);
+                            manifest.keep_below(dir);
+                        },
+                    }
+                }
+                if !attempt.student_submission.is_empty() {
+                    BBCourse::save_attempt_text(&mut manifest, &attempt_dir.join("submission.html"), &attempt.student_submission, &gbc.content_id, attempt, BBManifestFile::SUBMISSION)?;
+                }
+                if !attempt.feedback.is_empty() {
+                    BBCourse::save_attempt_text(&mut manifest, &attempt_dir.join("feedback.html"), &attempt.feedback, &gbc.content_id, attempt, BBManifestFile::FEEDBACK)?;
+                }
+            }
+        }
+        if !list_only {
+            manifest.prune(&[BBManifestFile::SUBMISSION, BBManifestFile::FEEDBACK], false);
+        }
+        manifest.refresh_sizes();
+        manifest.save()?;
+        history.save()?;
+        Ok(total_download_size)
+    }
+
+    // Matches column names and the titles of linked content items, preferring exact matches
+    pub fn find_gradebook_column(&self, session: &BBSession, query: &str) -> Result<BBGradebookColumn, Box<dyn std::error::Error>> {
+        let manifest = self.load_manifest()?;
+        let query_lower = query.to_lowercase();
+        let titles = |gbc: &BBGradebookColumn| {
+            let mut titles = vec![gbc.name.to_lowercase()];
+            if let Some(content) = manifest.contents.get(&gbc.content_id) {
+                titles.push(content.title.to_lowercase());
+            }
+            titles
+        };
+        let gradebook_columns = self.get_course_gradebook(session)?;
+        let (exact, partial): (Vec<BBGradebookColumn>, Vec<BBGradebookColumn>) = gradebook_columns.into_iter()
+            .filter(|gbc| titles(gbc).iter().any(|title| title.contains(&query_lower)))
+            .partition(|gbc| titles(gbc).iter().any(|title| title == &query_lower));
+        let mut matches = if exact.is_empty() { partial } else { exact };
+        match matches.len() {
+            0 => Err(format!("No assignment matching \"{}\" found", query).into()),
+            1 => Ok(matches.remove(0)),
+            _ => Err(format!("Several assignments match \"{}\": {}", query, matches.iter().map(|gbc| format!("\"{}\"", gbc.name)).collect::<Vec<String>>().join(", ")).into()),
+        }
+    }
+
+    // Columns without a grade for the user (HTTP 404) are paired with None
+    pub fn get_course_grades(&self, session: &BBSession, user_id: &str) -> Result<Vec<BBColumnGrade>, Box<dyn std::error::Error>> {
+        let gradebook_columns = self.get_course_gradebook(session)?;
+        Ok(gradebook_columns.into_iter().map(|gbc| {
+            let grade = self.download_course_grade_json(session, &gbc.id, user_id).ok().and_then(|json| BBGrade::from_json(json).ok());
+            (gbc, grade)
+        }).collect())
+    }
+
+    pub fn view_course_grades(&self, session: &BBSession, user_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let grades = self.get_course_grades(session, user_id)?;
+        let mut total_score = 0.0;
+        let mut total_possible = 0.0;
+        let mut graded_count = 0;
+        for (gbc, grade) in &grades {
+            if let Some(grade) = grade.as_ref().filter(|grade| grade.score.is_some() || !grade.text.is_empty()) {
+                grade.view(&gbc.name, gbc.possible);
+                graded_count += 1;
+                if let (Some(score), Some(possible)) = (grade.score, gbc.possible.filter(|p| *p > 0.0)) {
+                    if !grade.exempt {
+                        total_score += score;
+                        total_possible += possible;
+                    }
+                }
+            }
+        }
+        if graded_count == 0 {
+            println!("No grades found.");
+        } else if total_possible > 0.0 {
+            println!("TOTAL: {}/{} ({:.1} %)", total_score, total_possible, 100.0*total_score/total_possible);
+        }
+        println!();
+        Ok(())
+    }
+
     pub fn view(&self) {
-        println!("{}: {} {}", self.alias, self.course_code, self.semester);
+        if self.info.name.is_empty() {
+            println!("{}: {} {}", self.alias, self.course_code, self.semester);
+        } else {
+            println!("{}: {} {} ({})", self.alias, self.course_code, self.semester, self.info.name);
+        }
+    }
+
+    // Moves the course folder below archive_dir, along with the course details from courses.json
+    pub fn archive(&self, archive_dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(archive_dir)?;
+        let archive_path = archive_dir.join(self.out_dir.file_name().map(|name| name.to_os_string()).unwrap_or_else(|| self.alias.clone().into()));
+        if archive_path.exists() {
+            return Err(format!("{} already exists", archive_path.display()).into());
+        }
+        if self.out_dir.exists() {
+            std::fs::rename(&self.out_dir, &archive_path)?;
+        } else {
+            std::fs::create_dir_all(&archive_path)?;
+        }
+        let mut course_file = std::fs::File::create(archive_path.join(".bbcm_course.json"))?;
+        std::io::Write::write_all(&mut course_file, json::JsonValue::from(self).pretty(4).as_bytes())?;
+        Ok(archive_path)
+    }
+
+    // Local state is read from the work and out dirs; only the next deadline is fetched
+    pub fn view_long(&self, session: &BBSession, work_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        self.view();
+        println!("    Term: {}", if self.info.term_id.is_empty() { "<unknown>" } else { &self.info.term_id });
+        println!("    Available: {}", if self.info.available.is_empty() { "<unknown>" } else { &self.info.available });
+        if !self.info.external_id.is_empty() {
+            println!("    External id: {}", self.info.external_id);
+        }
+        if !self.info.instructors.is_empty() {
+            println!("    Instructors: {}", self.info.instructors.join(", "));
+        }
+        if !self.info.url.is_empty() {
+            println!("    URL: {}", self.info.url);
+        }
+        if self.last_tree_download.is_empty() {
+            println!("    Last sync: never");
+        } else {
+            println!("    Last sync: {} ({})", local_rfc2822(&self.last_tree_download), relative_to_now(&self.last_tree_download));
+        }
+        println!("    Local tree: {:.1} MB in {} files", path_size(&self.out_dir) as f64/1000000.0, self.load_manifest()?.files.len());
+        println!("    Unread announcements: {}", self.load_announcement_cache(work_dir)?.unread().len());
+        match self.get_next_deadline(session)? {
+            Some(gbc) => println!("    Next deadline: \"{}\" due {} ({})", gbc.name, local_rfc2822(&gbc.due), relative_to_now(&gbc.due)),
+            None => println!("    Next deadline: none"),
+        }
+        println!();
+        Ok(())
     }
 }
 
@@ -213,6 +953,7 @@ impl std::convert::From<&BBCourse> for json::JsonValue {
             out_dir: course.out_dir.as_os_str().to_str().unwrap(),
             id: course.id.clone(),
             last_tree_download: course.last_tree_download.clone(),
+            info: json::JsonValue::from(&course.info),
         }
     }
 }
\ No newline at end of file
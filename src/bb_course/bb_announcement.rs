@@ -1,8 +1,10 @@
-mod bb_user;
+pub mod bb_user;
+pub mod bb_announcement_cache;
 
-use bb_user::BBUser;
+use bb_user::BBUserCache;
 use crate::bb_session::BBSession;
 
+#[derive(Debug, Clone)]
 pub struct BBAnnouncement {
     pub id: String,
     pub title: String,
@@ -19,16 +21,7 @@ impl BBAnnouncement {
         let json_string = std::string::String::from_utf8(json)?;
         let parsed_json = json::parse(&json_string)?;
 
-        Ok(parsed_json["results"].members().map(|member| {
-            BBAnnouncement {
-                id: member["id"].to_string(),
-                title: member["title"].to_string(),
-                body: member["body"].to_string(),
-                creator: member["creator"].to_string(), 
-                created: member["created"].to_string(), 
-                modified: member["modified"].to_string(),
-            }
-        }).collect())
+        Ok(parsed_json["results"].members().map(BBAnnouncement::from).collect())
     }
 
     pub fn view(&self) {
@@ -43,15 +36,67 @@ impl BBAnnouncement {
         );
     }
 
-    pub fn view_with_name(&self, session: &BBSession) {
+    // Looks up the creators of all the announcements, so that they can be viewed with names
+    pub fn resolve_creators<'a>(announcements: impl IntoIterator<Item = &'a BBAnnouncement>, session: &BBSession, users: &mut BBUserCache) {
+        let creators: Vec<&str> = announcements.into_iter().map(|announcement| announcement.creator.as_str()).collect();
+        users.resolve(session, &creators);
+    }
+
+    pub fn view_with_name(&self, users: &BBUserCache) {
         println!("{}\nTITLE: {}\nCREATOR: {}\nCREATED: {}\nMODIFIED: {}\n{}\n{}\n",
             "*".repeat(BBAnnouncement::VIEW_WIDTH),
             self.title,
-            BBUser::name_by_id(session, &self.creator).expect("Failed to get announcement creator name"),
+            users.display_name(&self.creator),
+            self.created,
+            self.modified,
+            "-".repeat(BBAnnouncement::VIEW_WIDTH),
+            html2text::from_read(self.body.as_bytes(), BBAnnouncement::VIEW_WIDTH), 
+        );
+    }
+
+    pub fn view_with_course(&self, users: &BBUserCache, course_alias: &str) {
+        println!("{}\nCOURSE: {}\nTITLE: {}\nCREATOR: {}\nCREATED: {}\nMODIFIED: {}\n{}\n{}\n",
+            "*".repeat(BBAnnouncement::VIEW_WIDTH),
+            course_alias,
+            self.title,
+            users.display_name(&self.creator),
             self.created,
             self.modified,
             "-".repeat(BBAnnouncement::VIEW_WIDTH),
             html2text::from_read(self.body.as_bytes(), BBAnnouncement::VIEW_WIDTH), 
         );
     }
-}
\ No newline at end of file
+
+    // Case insensitive search in title and text body
+    pub fn contains_text(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        self.title.to_lowercase().contains(&query)
+            || html2text::from_read(self.body.as_bytes(), BBAnnouncement::VIEW_WIDTH).to_lowercase().contains(&query)
+    }
+}
+
+impl std::convert::From<&json::JsonValue> for BBAnnouncement {
+    fn from(member: &json::JsonValue) -> BBAnnouncement {
+        BBAnnouncement {
+            id: member["id"].to_string(),
+            title: member["title"].to_string(),
+            body: member["body"].to_string(),
+            creator: member["creator"].to_string(), 
+            created: member["created"].to_string(), 
+            modified: member["modified"].to_string(),
+        }
+    }
+}
+
+impl std::convert::From<&BBAnnouncement> for json::JsonValue {
+    fn from(announcement: &BBAnnouncement) -> json::JsonValue {
+        json::object!{
+            id: announcement.id.clone(),
+            title: announcement.title.clone(),
+            body: announcement.body.clone(),
+            creator: announcement.creator.clone(),
+            created: announcement.created.clone(),
+            modified: announcement.modified.clone(),
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::path::{Path, PathBuf};
+use std::io::{Read, Write};
+use super::BBAnnouncement;
+use crate::utils::time_utils::partial_cmp_dt;
+
+// A fetched announcement, along with the "modified" value it had when it was last shown and when it was last marked read,
+// and whether it has been reported as new to hooks
+struct BBAnnouncementEntry {
+    announcement: BBAnnouncement,
+    shown_modified: String,
+    read_modified: String,
+    reported: bool,
+}
+
+// Local copy of fetched announcements for one course
+pub struct BBAnnouncementCache {
+    path: PathBuf,
+    entries: Vec<BBAnnouncementEntry>,
+}
+
+impl BBAnnouncementCache {
+    pub fn load(path: &Path) -> Result<BBAnnouncementCache, Box<dyn std::error::Error>> {
+        let mut entries = Vec::new();
+        if path.exists() {
+            let mut json_string = String::new();
+            std::fs::File::open(path)?.read_to_string(&mut json_string)?;
+            for member in json::parse(&json_string)?.members() {
+                entries.push(BBAnnouncementEntry {
+                    announcement: BBAnnouncement::from(&member["announcement"]),
+                    shown_modified: member["shown_modified"].to_string(),
+                    read_modified: member["read_modified"].to_string(),
+                    reported: member["reported"].as_bool().unwrap_or(true), // Cached before announcements were reported
+                });
+            }
+        }
+        Ok(BBAnnouncementCache {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json_array = json::JsonValue::Array(self.entries.iter().map(|entry| {
+            json::object!{
+                announcement: json::JsonValue::from(&entry.announcement),
+                shown_modified: entry.shown_modified.clone(),
+                read_modified: entry.read_modified.clone(),
+                reported: entry.reported,
+            }
+        }).collect());
+        let mut cache_file = std::fs::File::create(&self.path)?;
+        cache_file.write_all(json_array.pretty(4).as_bytes())?;
+        Ok(())
+    }
+
+    // Inserts new announcements and replaces stored ones with the same id, keeping their read state.
+    pub fn update(&mut self, announcements: Vec<BBAnnouncement>) {
+        for announcement in announcements {
+            if let Some(entry) = self.entries.iter_mut().find(|entry| entry.announcement.id == announcement.id) {
+                entry.announcement = announcement;
+            } else {
+                self.entries.push(BBAnnouncementEntry {
+                    announcement,
+                    shown_modified: String::from("null"),
+                    read_modified: String::from("null"),
+                    reported: false,
+                });
+            }
+        }
+        self.entries.sort_by(|e1, e2| {
+            partial_cmp_dt(&e1.announcement.created, &e2.announcement.created).unwrap_or(std::cmp::Ordering::Equal)
+        });
+    }
+
+    pub fn announcements(&self) -> Vec<&BBAnnouncement> {
+        self.entries.iter().map(|entry| &entry.announcement).collect()
+    }
+
+    // Announcements that have never been marked read, or have been edited since.
+    pub fn unread(&self) -> Vec<&BBAnnouncement> {
+        self.entries.iter()
+            .filter(|entry| entry.announcement.modified != entry.read_modified)
+            .map(|entry| &entry.announcement)
+            .collect()
+    }
+
+    // Records the announcements as shown to the user, in their current version.
+    // Returns the ones that are new, as they haven't been reported before.
+    pub fn mark_shown<'a>(&mut self, ids: impl IntoIterator<Item = &'a String>) -> Vec<BBAnnouncement> {
+        let ids: Vec<&String> = ids.into_iter().collect();
+        for entry in self.entries.iter_mut().filter(|entry| ids.contains(&&entry.announcement.id)) {
+            entry.shown_modified = entry.announcement.modified.clone();
+        }
+        self.mark_reported(ids)
+    }
+
+    // Records the announcements as reported to hooks, and returns the ones that weren't reported before
+    pub fn mark_reported<'a>(&mut self, ids: impl IntoIterator<Item = &'a String>) -> Vec<BBAnnouncement> {
+        let ids: Vec<&String> = ids.into_iter().collect();
+        let mut reported = Vec::new();
+        for entry in self.entries.iter_mut().filter(|entry| !entry.reported && ids.contains(&&entry.announcement.id)) {
+            entry.reported = true;
+            reported.push(entry.announcement.clone());
+        }
+        reported
+    }
+
+    // Only announcements that have been shown since they were last edited are marked read,
+    // so that announcements fetched after the user last looked stay unread.
+    pub fn mark_read(&mut self) -> usize {
+        let mut marked = 0;
+        for entry in &mut self.entries {
+            if entry.announcement.modified != entry.read_modified && entry.announcement.modified == entry.shown_modified {
+                entry.read_modified = entry.announcement.modified.clone();
+                marked += 1;
+            }
+        }
+        marked
+    }
+}
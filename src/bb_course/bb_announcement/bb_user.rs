@@ -1,14 +1,22 @@
+use std::path::{Path, PathBuf};
+use std::io::{Read, Write};
+use std::collections::{HashMap, HashSet};
 use crate::bb_session::BBSession;
+use crate::utils::time_utils::unix_now;
 
+#[derive(Debug, Clone)]
 pub struct BBUser {
-    _user_name: String,
-    name: String,
-    _id: String,
+    pub user_name: String,
+    pub name: String,
+    pub given_name: String,
+    pub family_name: String,
+    pub email: String,
+    pub id: String,
 }
 
 impl BBUser {
 
-    fn get_by_id(session: &BBSession, id: &str) -> Result<BBUser, Box<dyn std::error::Error>> {
+    pub fn get_by_id(session: &BBSession, id: &str) -> Result<BBUser, Box<dyn std::error::Error>> {
         let url = format!("https://{}/learn/api/public/v1/users/{}", session.domain, id);
 
         let bytes = session.download_bytes(&url)?;
@@ -16,15 +24,124 @@ impl BBUser {
         let json_string = std::string::String::from_utf8(bytes)?;
         let parsed_json = json::parse(&json_string)?;
 
-        Ok(BBUser {
-            _user_name: parsed_json["userName"].to_string(),
-            name: format!("{} {}", parsed_json["name"]["given"], parsed_json["name"]["family"]),
-            _id: parsed_json["id"].to_string(),
+        Ok(BBUser::from(&parsed_json))
+    }
+
+    pub fn get_current(session: &BBSession) -> Result<BBUser, Box<dyn std::error::Error>> {
+        BBUser::get_by_id(session, "me")
+    }
+}
+
+impl std::convert::From<&json::JsonValue> for BBUser {
+    fn from(member: &json::JsonValue) -> BBUser {
+        let given_name = member["name"]["given"].as_str().unwrap_or("").trim().to_string();
+        let family_name = member["name"]["family"].as_str().unwrap_or("").trim().to_string();
+        // Users without a visible name are shown by their user name
+        let name = [given_name.as_str(), family_name.as_str()].iter().filter(|part| !part.is_empty()).copied().collect::<Vec<&str>>().join(" ");
+        BBUser {
+            user_name: member["userName"].to_string(),
+            name: if name.is_empty() { member["userName"].to_string() } else { name },
+            given_name,
+            family_name,
+            email: member["contact"]["email"].as_str().unwrap_or("").to_string(), // Only present where visible
+            id: member["id"].to_string(),
+        }
+    }
+}
+
+// Users fetched earlier, with the unix time they were fetched at. Entries older than the TTL are fetched again.
+pub struct BBUserCache {
+    path: PathBuf,
+    ttl: i64,
+    entries: HashMap<String, (BBUser, i64)>,
+    failed: HashSet<String>, // Lookups that failed during this run, e.g. because the profile isn't visible to students
+}
+
+impl BBUserCache {
+    pub const DEFAULT_TTL: i64 = 7*24*60*60;
+
+    pub fn load(path: &Path, ttl: i64) -> Result<BBUserCache, Box<dyn std::error::Error>> {
+        let mut entries = HashMap::new();
+        if path.exists() {
+            let mut json_string = String::new();
+            std::fs::File::open(path)?.read_to_string(&mut json_string)?;
+            for (id, member) in json::parse(&json_string)?.entries() {
+                let user = BBUser {
+                    user_name: member["user_name"].to_string(),
+                    name: member["name"].to_string(),
+                    given_name: member["given_name"].as_str().unwrap_or("").to_string(),
+                    family_name: member["family_name"].as_str().unwrap_or("").to_string(),
+                    email: member["email"].as_str().unwrap_or("").to_string(),
+                    id: id.to_string(),
+                };
+                entries.insert(id.to_string(), (user, member["fetched"].as_i64().unwrap_or(0)));
+            }
+        }
+        Ok(BBUserCache {
+            path: path.to_path_buf(),
+            ttl,
+            entries,
+            failed: HashSet::new(),
         })
     }
 
-    pub fn name_by_id(session: &BBSession, id: &str) -> Result<String, Box<dyn std::error::Error>> {
-        Ok(BBUser::get_by_id(session, id)?.name)
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut json_object = json::JsonValue::new_object();
+        for (user, fetched) in self.entries.values() {
+            json_object[user.id.as_str()] = json::object!{
+                user_name: user.user_name.clone(),
+                name: user.name.clone(),
+                given_name: user.given_name.clone(),
+                family_name: user.family_name.clone(),
+                email: user.email.clone(),
+                fetched: *fetched,
+            };
+        }
+        let mut cache_file = std::fs::File::create(&self.path)?;
+        cache_file.write_all(json_object.pretty(4).as_bytes())?;
+        Ok(())
+    }
+
+    fn is_fresh(&self, id: &str) -> bool {
+        self.entries.get(id).map(|(_, fetched)| unix_now() - fetched < self.ttl).unwrap_or(false)
+    }
+
+    // Fetches all missing or expired users in parallel, each id only once
+    pub fn resolve(&mut self, session: &BBSession, ids: &[&str]) {
+        let mut missing: Vec<String> = ids.iter()
+            .filter(|id| !self.is_fresh(id) && !self.failed.contains(**id))
+            .map(|id| id.to_string())
+            .collect();
+        missing.sort();
+        missing.dedup();
+        let threads: Vec<_> = missing.into_iter().map(|id| {
+            let session = session.clone();
+            std::thread::spawn(move || {
+                let result = BBUser::get_by_id(&session, &id).map_err(|err| err.to_string());
+                (id, result)
+            })
+        }).collect();
+        for thread in threads {
+            match thread.join().expect("Failed to join thread") {
+                (id, Ok(user)) => {
+                    self.entries.insert(id, (user, unix_now()));
+                },
+                (id, Err(err)) => {
+                    if self.entries.contains_key(&id) {
+                        eprintln!("Note: Could not refresh user {}, using cached name: {}", id, err);
+                    } else {
+                        eprintln!("Note: Could not look up user {}: {}", id, err);
+                    }
+                    self.failed.insert(id);
+                },
+            }
+        }
+    }
+
+    // Expired entries are still used if they could not be refreshed
+    pub fn display_name(&self, id: &str) -> String {
+        self.entries.get(id)
+            .map(|(user, _)| user.name.clone())
+            .unwrap_or_else(|| format!("unknown user ({})", id))
     }
 }
-    
\ No newline at end of file
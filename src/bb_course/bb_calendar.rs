@@ -0,0 +1,30 @@
+pub struct BBCalendarItem {
+    pub id: String,
+    pub item_type: String,
+    pub title: String,
+    pub description: String,
+    pub location: String,
+    pub start: String,
+    pub end: String,
+    pub modified: String,
+}
+
+impl BBCalendarItem {
+    pub fn vec_from_json_results(json: Vec<u8>) -> Result<Vec<BBCalendarItem>, Box<dyn std::error::Error>> {
+        let json_string = std::string::String::from_utf8(json)?;
+        let parsed_json = json::parse(&json_string)?;
+
+        Ok(parsed_json["results"].members().map(|member| {
+            BBCalendarItem {
+                id: member["id"].to_string(),
+                item_type: member["type"].to_string(),
+                title: member["title"].to_string(),
+                description: member["description"].as_str().unwrap_or("").to_string(),
+                location: member["location"].as_str().unwrap_or("").to_string(),
+                start: member["start"].to_string(),
+                end: member["end"].to_string(),
+                modified: member["modified"].to_string(),
+            }
+        }).collect())
+    }
+}
@@ -4,15 +4,17 @@ pub mod bb_content_classes;
 use std::path::Path;
 use std::io::Write;
 use std::thread::JoinHandle;
-use crate::bb_session::BBSession;
+use crate::bb_session::{BBSession, BBValidators};
 use crate::bb_course::BBCourse;
+use crate::bb_course::bb_manifest::{BBManifest, BBManifestContent, BBManifestFile};
 use crate::utils::filename_utils::{valid_filename, valid_dir_name};
-use crate::utils::time_utils::partial_cmp_dt;
-use bb_attachment::BBAttachment;
+use crate::utils::time_utils::{partial_cmp_dt, utc_now};
+use bb_attachment::{BBAttachment, BBAttachmentDownload};
 
 pub struct BBContent<'a> {
     pub course: &'a BBCourse,
     pub id: String,
+    pub parent_id: String,
     pub title: String,
     pub modified: String,
     pub content_handler: String,
@@ -20,30 +22,40 @@ pub struct BBContent<'a> {
 }
 
 impl<'a> BBContent<'a> {
-    pub const DEFAULT_FIELDS: &'static str = "fields=id,title,modified,contentHandler,links"; // Looks like all contentHandlers have these fields (not attachments, though).
+    pub const DEFAULT_FIELDS: &'static str = "fields=id,parentId,title,modified,contentHandler,links"; // Looks like all contentHandlers have these fields (not attachments, though).
 
     pub fn vec_from_json_results(json: Vec<u8>, course: &'a BBCourse) -> Result<Vec<BBContent<'a>>, Box<dyn std::error::Error>> {
         let json_string = std::string::String::from_utf8(json)?;
         let parsed_json = json::parse(&json_string)?;
 
-        Ok(parsed_json["results"].members().map(|m1| {
-            BBContent {
-                course,
-                id: m1["id"].to_string(),
-                title: m1["title"].to_string(),
-                modified: m1["modified"].to_string(),
-                content_handler: m1["contentHandler"]["id"].to_string(),
-                links: m1["links"].members().map(|m2| m2["href"].to_string()).collect(),
-            }
-        }).collect())
+        Ok(parsed_json["results"].members().map(|m1| BBContent::from_json_value(m1, course)).collect())
+    }
+
+    pub fn from_json(json: Vec<u8>, course: &'a BBCourse) -> Result<BBContent<'a>, Box<dyn std::error::Error>> {
+        let json_string = std::string::String::from_utf8(json)?;
+        let parsed_json = json::parse(&json_string)?;
+
+        Ok(BBContent::from_json_value(&parsed_json, course))
+    }
+
+    fn from_json_value(m1: &json::JsonValue, course: &'a BBCourse) -> BBContent<'a> {
+        BBContent {
+            course,
+            id: m1["id"].to_string(),
+            parent_id: m1["parentId"].to_string(),
+            title: m1["title"].to_string(),
+            modified: m1["modified"].to_string(),
+            content_handler: m1["contentHandler"]["id"].to_string(),
+            links: m1["links"].members().map(|m2| m2["href"].to_string()).collect(),
+        }
     }
 
-    fn get_children(&self, session: &BBSession) -> Result<Vec<BBContent>, Box<dyn std::error::Error>> {
+    fn get_children(&self, session: &BBSession) -> Result<Vec<BBContent<'_>>, Box<dyn std::error::Error>> {
         let json = self.download_children_json(session, &[BBContent::DEFAULT_FIELDS])?;
         BBContent::vec_from_json_results(json, self.course)
     }
 
-    fn get_attachments(&self, session: &BBSession) -> Result<Vec<BBAttachment>, Box<dyn std::error::Error>> {
+    fn get_attachments(&self, session: &BBSession) -> Result<Vec<BBAttachment<'_, '_>>, Box<dyn std::error::Error>> {
         let json = self.download_attachments_json(session)?;
         BBAttachment::vec_from_json_results(json, self)
     }
@@ -71,42 +83,61 @@ impl<'a> BBContent<'a> {
         session.download_bytes(&url)
     }
 
+    fn record(&self, manifest: &mut BBManifest, path: &Path) {
+        manifest.record_content(BBManifestContent {
+            id: self.id.clone(),
+            parent_id: self.parent_id.clone(),
+            title: self.title.clone(),
+            content_handler: self.content_handler.clone(),
+            modified: self.modified.clone(),
+            path: path.to_path_buf(),
+        });
+    }
+
     pub fn download_children(&self, 
         session: &BBSession, 
         out_path: &Path, 
         overwrite: bool,
-        threads: &mut Vec<JoinHandle<f64>>
+        threads: &mut Vec<JoinHandle<BBAttachmentDownload>>,
+        manifest: &mut BBManifest,
     ) -> Result<(), Box<dyn std::error::Error>> {
         if bb_content_classes::ATTACHABLE.contains(&self.content_handler.as_str()) {
+            let attachments_path = out_path.join(valid_dir_name(&self.title));
+            self.record(manifest, &attachments_path);
             let maybe_updated = partial_cmp_dt(&self.modified, &self.course.last_tree_download).map(|o| o == std::cmp::Ordering::Greater);
             if overwrite || maybe_updated.is_none() || maybe_updated.is_some() && maybe_updated.unwrap() {
-                let attachments_path = out_path.join(valid_dir_name(&self.title));
                 std::fs::create_dir_all(&attachments_path).expect("Error creating attachment files dir"); 
-                self.download_attachments(session, &attachments_path, threads)
+                self.download_attachments(session, &attachments_path, overwrite, threads, manifest)
             } else {
+                manifest.keep_content_files(&self.id);
                 Ok(())
             }
         } else if self.content_handler == bb_content_classes::FOLDER {
             // "modified" for folders don't reflect their content, so no need in checking it.
             let children_path = out_path.join(valid_dir_name(&self.title));
+            self.record(manifest, &children_path);
             std::fs::create_dir_all(&children_path).expect("Error creating children dir"); 
             match self.get_children(session) {
                 Ok(children) => {
                     for child in children {
-                        child.download_children(session, &children_path, overwrite, threads)?;
+                        child.download_children(session, &children_path, overwrite, threads, manifest)?;
                     }
                 },
                 Err(err) => {
                     //TODO: Graceful handling only for HTTP 403
                     eprintln!("Error downloading children for \"{}\": {}", self.title, err);
+                    manifest.keep_below(&children_path);
                 }
             }
             Ok(())
         } else {
+            self.record(manifest, out_path);
             let maybe_updated = partial_cmp_dt(&self.modified, &self.course.last_tree_download).map(|o| o == std::cmp::Ordering::Greater);
             if overwrite || maybe_updated.is_none() || maybe_updated.is_some() && maybe_updated.unwrap() {
                 // eprintln!("No branching action defined for {} with content handler {:?}; saving links file instead", self.title, self.content_handler);
-                self.create_url_files(&session.domain, out_path)?;
+                self.create_url_files(&session.domain, out_path, manifest)?;
+            } else {
+                manifest.keep_content_files(&self.id);
             }
             Ok(())
         }
@@ -117,27 +148,63 @@ impl<'a> BBContent<'a> {
         &self, 
         session: &BBSession, 
         out_path: &Path,
-        threads: &mut Vec<JoinHandle<f64>>
+        overwrite: bool,
+        threads: &mut Vec<JoinHandle<BBAttachmentDownload>>,
+        manifest: &mut BBManifest,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let content_attachments = self.get_attachments(session)?;
         for attachment in content_attachments {
             let file_path = out_path.join(valid_filename(&attachment.filename));
-            attachment.download(session, &file_path, threads)?;
+            let local_path = attachment.local_path(&file_path);
+            let validators = manifest.files.get(&manifest.relative_path(&local_path)).map(|file| file.validators.clone());
+            attachment.download(session, &file_path, validators.clone().filter(|_| !overwrite), threads)?;
+            manifest.record_file(BBManifestFile {
+                path: local_path,
+                content_id: self.id.clone(),
+                source_id: attachment.id.clone(),
+                kind: String::from(BBManifestFile::ATTACHMENT),
+                mimetype: attachment.mimetype.clone(),
+                modified: self.modified.clone(),
+                size: 0,
+                synced: utc_now(),
+                validators: validators.unwrap_or_default(),
+            });
         }
         Ok(())
     }
 
-    fn create_url_files(&self, domain: &str, out_dir: &Path) -> Result<f64, Box<dyn std::error::Error>> {
+    fn create_url_files(&self, domain: &str, out_dir: &Path, manifest: &mut BBManifest) -> Result<f64, Box<dyn std::error::Error>> {
         let mut sum = 0.0;
         for link in &self.links {
             let out_path = out_dir.join(format!("{}.url", valid_filename(&self.title)));
-            let mut url_file = std::fs::File::create(out_path).expect("Error creating URL file");
-            write!(url_file, "[InternetShortcut]\nURL=https://{}{}", domain, link).unwrap();
-            sum += url_file.metadata()?.len() as f64;
+            let url_file_content = format!("[InternetShortcut]\nURL=https://{}{}", domain, link);
+            // Unchanged links keep the time they were last written
+            let previously_synced = manifest.files.get(&manifest.relative_path(&out_path)).map(|file| file.synced.clone());
+            let synced = match previously_synced {
+                Some(synced) if std::fs::read_to_string(&out_path).map(|content| content == url_file_content).unwrap_or(false) => synced,
+                _ => {
+                    let mut url_file = std::fs::File::create(&out_path).expect("Error creating URL file");
+                    url_file.write_all(url_file_content.as_bytes())?;
+                    sum += url_file_content.len() as f64;
+                    utc_now()
+                },
+            };
+            manifest.record_file(BBManifestFile {
+                path: out_path,
+                content_id: self.id.clone(),
+                source_id: String::new(),
+                kind: String::from(BBManifestFile::LINK),
+                mimetype: String::from("application/internet-shortcut"),
+                modified: self.modified.clone(),
+                size: 0,
+                synced,
+                validators: BBValidators::default(),
+            });
         }
         Ok(sum)
     }
 
+    #[allow(dead_code)]
     pub fn view(&self) {
         println!("TITLE: {}\nCONTENT HANDLER: {:?}\n",
             self.title,
@@ -1,7 +1,8 @@
 use super::BBContent;
-use crate::bb_session::BBSession;
-use std::path::Path;
-use std::io::Cursor;
+use crate::bb_session::{BBSession, BBValidators, BBConditionalDownload};
+use crate::bb_course::bb_history::BBHistory;
+use crate::utils::fs_utils::{path_size, remove_path};
+use std::path::{Path, PathBuf};
 use std::thread::JoinHandle;
 
 pub struct BBAttachment<'a, 'b> {
@@ -11,6 +12,16 @@ pub struct BBAttachment<'a, 'b> {
     pub mimetype: String,
 }
 
+// What a download thread did with an attachment
+pub struct BBAttachmentDownload {
+    pub path: PathBuf, // Local path, as recorded in the manifest
+    pub size: f64,
+    pub saved: f64, // Size of a local copy that was kept
+    pub validators: Option<BBValidators>, // None when the local copy was kept, or a zip file could not be unzipped
+    pub replaced: Option<PathBuf>, // The earlier version, set aside for the history, if the content changed
+    pub changed: bool, // False when the local copy was kept, or downloaded again with the same content
+}
+
 impl<'a, 'b> BBAttachment<'a, 'b> {
     pub fn vec_from_json_results(json: Vec<u8>, content: &'a BBContent<'b>) -> Result<Vec<BBAttachment<'a, 'b>>, Box<dyn std::error::Error>> {
         let json_string = std::string::String::from_utf8(json)?;
@@ -26,7 +37,21 @@ impl<'a, 'b> BBAttachment<'a, 'b> {
         }).collect())
     }
 
-    pub fn download(&self, session: &BBSession, out_path: &Path, threads: &mut Vec<JoinHandle<f64>>) -> Result<(), Box<dyn std::error::Error>> {
+    fn is_zip(&self) -> bool {
+        self.mimetype == "application/zip"
+    }
+
+    // Zip attachments are unzipped into a folder next to where the file would have been
+    pub fn local_path(&self, out_path: &Path) -> std::path::PathBuf {
+        if self.is_zip() {
+            out_path.with_extension("")
+        } else {
+            out_path.to_path_buf()
+        }
+    }
+
+    // Validators are only sent when the local copy still exists
+    pub fn download(&self, session: &BBSession, out_path: &Path, validators: Option<BBValidators>, threads: &mut Vec<JoinHandle<BBAttachmentDownload>>) -> Result<(), Box<dyn std::error::Error>> {
         
         let url = format!("https://{}/learn/api/public/v1/courses/{}/contents/{}/attachments/{}/download",
         session.domain,
@@ -36,28 +61,87 @@ impl<'a, 'b> BBAttachment<'a, 'b> {
         
         let session = session.clone(); // Session is quite cheap to clone. session: Arc<BBSession> might be slightly faster. Maybe even Rc<BBSession> works, since it's never sent?
         let out_path = std::path::PathBuf::from(out_path);
-        let is_zip = self.mimetype == "application/zip";
+        let local_path = self.local_path(&out_path);
+        let validators = validators.filter(|_| local_path.exists());
+        let is_zip = self.is_zip();
         threads.push(std::thread::spawn(move || {
             if is_zip { 
-                println!("Downloading and unzipping {:?}", out_path.file_name().unwrap());
-                let bytes = session.download_bytes(&url).unwrap();
-                let download_size = bytes.len() as f64;
-                let out_dir = out_path.with_extension("");
-                let unzip_result = zip_extract::extract(Cursor::new(bytes), &out_dir, true); // zip_extract explicitly wants &PathBuf
-                if unzip_result.is_ok() { 
-                    download_size // Consider returning size of unzipped folder
-                } else {
-                    eprintln!("Note: Unzipping of {:?} failed", out_path);
-                    0.0
-                }
+                eprintln!("Downloading and unzipping {:?}", out_path.file_name().unwrap());
             } else {
-                println!("Downloading {:?}", out_path.file_name().unwrap());
-                session.download_file(&url, &out_path).unwrap()
+                eprintln!("Downloading {:?}", out_path.file_name().unwrap());
+            }
+            // Zip folders are set aside once a new zip file has been downloaded
+            let previous_path = if is_zip { None } else { BBHistory::set_aside(&local_path) };
+            let download = session.download_file_if_changed(&url, &out_path, validators.as_ref()).unwrap_or_else(|err| {
+                if let Some(previous_path) = &previous_path {
+                    BBHistory::discard(previous_path);
+                }
+                panic!("Error downloading {:?}: {}", out_path, err)
+            });
+            match download {
+                BBConditionalDownload::Unchanged => {
+                    if let Some(previous_path) = &previous_path {
+                        BBHistory::discard(previous_path);
+                    }
+                    BBAttachmentDownload {
+                        path: local_path.clone(),
+                        size: 0.0,
+                        saved: path_size(&local_path) as f64,
+                        validators: None,
+                        replaced: None,
+                        changed: false,
+                    }
+                },
+                BBConditionalDownload::Downloaded { size, validators } => {
+                    let mut previous_path = previous_path;
+                    if is_zip {
+                        // Unzipped next to the folder first, so that the earlier folder is only replaced once unzipping succeeded.
+                        // Extracted files may be linked to the content store, so they are replaced instead of overwritten.
+                        let unzip_path = local_path.with_file_name(format!("{}.bbcm-unzip", local_path.file_name().unwrap().to_string_lossy()));
+                        let _ = remove_path(&unzip_path);
+                        let unzip_result = std::fs::File::open(&out_path).map_err(|err| err.to_string())
+                            .and_then(|zip_file| zip_extract::extract(zip_file, &unzip_path, true).map_err(|err| err.to_string())) // zip_extract explicitly wants &PathBuf
+                            .and_then(|_| {
+                                previous_path = BBHistory::set_aside(&local_path);
+                                std::fs::rename(&unzip_path, &local_path).map_err(|err| {
+                                    if let Some(previous_path) = previous_path.take() {
+                                        let _ = std::fs::rename(previous_path, &local_path);
+                                    }
+                                    err.to_string()
+                                })
+                            });
+                        // The earlier folder and the zip file are kept, and the new validators aren't recorded, so the next sync tries again
+                        if let Err(err) = unzip_result {
+                            eprintln!("Note: Unzipping of {:?} failed, the zip file is kept: {}", out_path, err);
+                            let _ = remove_path(&unzip_path);
+                            return BBAttachmentDownload {
+                                path: local_path,
+                                size,
+                                saved: 0.0,
+                                validators: None,
+                                replaced: None,
+                                changed: false,
+                            };
+                        }
+                        let _ = std::fs::remove_file(&out_path);
+                    }
+                    let existed = previous_path.is_some();
+                    let replaced = previous_path.and_then(|previous_path| BBHistory::changed(previous_path, &local_path));
+                    BBAttachmentDownload {
+                        changed: !existed || replaced.is_some(),
+                        replaced,
+                        path: local_path,
+                        size,
+                        saved: 0.0,
+                        validators: Some(validators),
+                    }
+                },
             }
         }));
         Ok(())
     }
 
+    #[allow(dead_code)]
     pub fn view(&self) {
         println!("FILENAME: {}\nMIMETYPE: {}\n", self.filename, self.mimetype)
     }
@@ -0,0 +1,61 @@
+// Course details that are kept in courses.json and refreshed from the courses API
+#[derive(Debug, Clone, Default)]
+pub struct BBCourseInfo {
+    pub name: String,
+    pub term_id: String,
+    pub available: String,
+    pub external_id: String,
+    pub instructors: Vec<String>,
+    pub url: String,
+    pub refreshed: String,
+}
+
+impl BBCourseInfo {
+    // Instructors are looked up separately, through the course memberships
+    pub fn from_json(json: Vec<u8>) -> Result<BBCourseInfo, Box<dyn std::error::Error>> {
+        let json_string = std::string::String::from_utf8(json)?;
+        let parsed_json = json::parse(&json_string)?;
+
+        Ok(BBCourseInfo {
+            name: parsed_json["name"].to_string(),
+            term_id: parsed_json["termId"].as_str().unwrap_or("").to_string(),
+            available: parsed_json["availability"]["available"].as_str().unwrap_or("").to_string(),
+            external_id: parsed_json["externalId"].as_str().unwrap_or("").to_string(),
+            instructors: Vec::new(),
+            url: parsed_json["externalAccessUrl"].as_str().unwrap_or("").to_string(),
+            refreshed: String::new(),
+        })
+    }
+
+    pub fn is_refreshed(&self) -> bool {
+        !self.refreshed.is_empty()
+    }
+}
+
+impl std::convert::From<&json::JsonValue> for BBCourseInfo {
+    fn from(member: &json::JsonValue) -> BBCourseInfo {
+        BBCourseInfo {
+            name: member["name"].as_str().unwrap_or("").to_string(),
+            term_id: member["term_id"].as_str().unwrap_or("").to_string(),
+            available: member["available"].as_str().unwrap_or("").to_string(),
+            external_id: member["external_id"].as_str().unwrap_or("").to_string(),
+            instructors: member["instructors"].members().map(|instructor| instructor.to_string()).collect(),
+            url: member["url"].as_str().unwrap_or("").to_string(),
+            refreshed: member["refreshed"].as_str().unwrap_or("").to_string(),
+        }
+    }
+}
+
+impl std::convert::From<&BBCourseInfo> for json::JsonValue {
+    fn from(info: &BBCourseInfo) -> json::JsonValue {
+        json::object!{
+            name: info.name.clone(),
+            term_id: info.term_id.clone(),
+            available: info.available.clone(),
+            external_id: info.external_id.clone(),
+            instructors: info.instructors.clone(),
+            url: info.url.clone(),
+            refreshed: info.refreshed.clone(),
+        }
+    }
+}
@@ -0,0 +1,21 @@
+pub mod bb_message;
+pub mod bb_discussion_cache;
+
+pub struct BBDiscussion {
+    pub id: String,
+    pub title: String,
+}
+
+impl BBDiscussion {
+    pub fn vec_from_json_results(json: Vec<u8>) -> Result<Vec<BBDiscussion>, Box<dyn std::error::Error>> {
+        let json_string = std::string::String::from_utf8(json)?;
+        let parsed_json = json::parse(&json_string)?;
+
+        Ok(parsed_json["results"].members().map(|member| {
+            BBDiscussion {
+                id: member["id"].to_string(),
+                title: member["title"].as_str().or_else(|| member["name"].as_str()).unwrap_or("").to_string(),
+            }
+        }).collect())
+    }
+}
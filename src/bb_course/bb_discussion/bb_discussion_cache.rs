@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+use std::io::{Read, Write};
+use std::collections::HashMap;
+use super::bb_message::BBMessage;
+use crate::utils::time_utils::partial_cmp_dt;
+
+// Local copy of the discussion messages of one course. Thread documents are only written again when a message in the thread was added, edited or removed.
+pub struct BBDiscussionCache {
+    path: PathBuf,
+    messages: HashMap<String, BBMessage>,
+}
+
+impl BBDiscussionCache {
+    pub fn load(path: &Path) -> Result<BBDiscussionCache, Box<dyn std::error::Error>> {
+        let mut messages = HashMap::new();
+        if path.exists() {
+            let mut json_string = String::new();
+            std::fs::File::open(path)?.read_to_string(&mut json_string)?;
+            for member in json::parse(&json_string)?.members() {
+                let message = BBMessage::from(member);
+                messages.insert(message.id.clone(), message);
+            }
+        }
+        Ok(BBDiscussionCache {
+            path: path.to_path_buf(),
+            messages,
+        })
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut messages: Vec<&BBMessage> = self.messages.values().collect();
+        messages.sort_by(|m1, m2| m1.id.cmp(&m2.id));
+        let json_array = json::JsonValue::Array(messages.into_iter().map(json::JsonValue::from).collect());
+        let mut cache_file = std::fs::File::create(&self.path)?;
+        cache_file.write_all(json_array.pretty(4).as_bytes())?;
+        Ok(())
+    }
+
+    // Whether the replies of a thread can be skipped: replies don't change the "modified" of the thread, so this needs a reply count
+    pub fn is_thread_unchanged(&self, thread: &BBMessage) -> bool {
+        match (thread.reply_count, self.messages.get(&thread.id)) {
+            (Some(reply_count), Some(cached)) => {
+                cached.modified == thread.modified && cached.reply_count == Some(reply_count) && self.replies(&thread.id).len() == reply_count
+            },
+            _ => false,
+        }
+    }
+
+    // Replies don't change the "modified" of the thread, so they are compared one by one
+    pub fn is_up_to_date(&self, thread: &BBMessage, replies: &[BBMessage]) -> bool {
+        let cached_replies = self.replies(&thread.id);
+        cached_replies.len() == replies.len() && std::iter::once(thread).chain(replies).all(|message| {
+            self.messages.get(&message.id).map(|cached| cached.modified == message.modified).unwrap_or(false)
+        })
+    }
+
+    // Replaces the cached thread and its replies, dropping replies that were removed
+    pub fn update_thread(&mut self, thread: BBMessage, replies: Vec<BBMessage>) {
+        let removed: Vec<String> = self.replies(&thread.id).into_iter()
+            .filter(|cached| !replies.iter().any(|reply| reply.id == cached.id))
+            .map(|cached| cached.id.clone())
+            .collect();
+        for id in removed {
+            self.messages.remove(&id);
+        }
+        for message in std::iter::once(thread).chain(replies) {
+            self.messages.insert(message.id.clone(), message);
+        }
+    }
+
+    // All cached messages below a thread, in order of creation
+    pub fn replies(&self, thread_id: &str) -> Vec<&BBMessage> {
+        let mut replies = Vec::new();
+        let mut parents = vec![thread_id];
+        while let Some(parent_id) = parents.pop() {
+            for message in self.messages.values().filter(|message| message.parent_id == parent_id) {
+                parents.push(&message.id);
+                replies.push(message);
+            }
+        }
+        replies.sort_by(|m1, m2| partial_cmp_dt(&m1.created, &m2.created).unwrap_or(std::cmp::Ordering::Equal));
+        replies
+    }
+}
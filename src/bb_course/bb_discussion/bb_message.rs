@@ -0,0 +1,71 @@
+#[derive(Debug, Clone)]
+pub struct BBMessage {
+    pub id: String,
+    pub discussion_id: String,
+    pub parent_id: String,
+    pub title: String,
+    pub body: String,
+    pub creator: String, // User id
+    pub created: String,
+    pub modified: String,
+    pub reply_count: Option<usize>, // Of a thread, where the Learn version includes it
+}
+
+impl BBMessage {
+    pub fn vec_from_json_results(json: Vec<u8>, discussion_id: &str) -> Result<Vec<BBMessage>, Box<dyn std::error::Error>> {
+        let json_string = std::string::String::from_utf8(json)?;
+        let parsed_json = json::parse(&json_string)?;
+
+        Ok(parsed_json["results"].members().map(|member| {
+            // Field names differ slightly between Learn versions
+            let body = member["body"].as_str()
+                .or_else(|| member["body"]["rawText"].as_str())
+                .or_else(|| member["body"]["displayText"].as_str())
+                .unwrap_or("");
+            let creator = if member["creatorId"].is_null() { &member["userId"] } else { &member["creatorId"] };
+            BBMessage {
+                id: member["id"].to_string(),
+                discussion_id: discussion_id.to_string(),
+                parent_id: member["parentId"].to_string(),
+                title: member["title"].as_str().unwrap_or("").to_string(),
+                body: body.to_string(),
+                creator: creator.to_string(),
+                created: member["created"].to_string(),
+                modified: member["modified"].to_string(),
+                reply_count: member["replyCount"].as_usize(),
+            }
+        }).collect())
+    }
+}
+
+impl std::convert::From<&json::JsonValue> for BBMessage {
+    fn from(member: &json::JsonValue) -> BBMessage {
+        BBMessage {
+            id: member["id"].to_string(),
+            discussion_id: member["discussion_id"].to_string(),
+            parent_id: member["parent_id"].to_string(),
+            title: member["title"].to_string(),
+            body: member["body"].to_string(),
+            creator: member["creator"].to_string(),
+            created: member["created"].to_string(),
+            modified: member["modified"].to_string(),
+            reply_count: member["reply_count"].as_usize(),
+        }
+    }
+}
+
+impl std::convert::From<&BBMessage> for json::JsonValue {
+    fn from(message: &BBMessage) -> json::JsonValue {
+        json::object!{
+            id: message.id.clone(),
+            discussion_id: message.discussion_id.clone(),
+            parent_id: message.parent_id.clone(),
+            title: message.title.clone(),
+            body: message.body.clone(),
+            creator: message.creator.clone(),
+            created: message.created.clone(),
+            modified: message.modified.clone(),
+            reply_count: message.reply_count,
+        }
+    }
+}
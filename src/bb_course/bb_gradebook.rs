@@ -1,10 +1,38 @@
-use crate::utils::time_utils::local_rfc2822;
+pub mod bb_grade;
+pub mod bb_attempt;
+pub mod bb_submission;
+
+use crate::utils::time_utils::{local_rfc2822, local_days_from_today, partial_cmp_dt, relative_to_now, utc_now};
+use bb_grade::BBGrade;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Urgency {
+    Overdue,
+    Today,
+    ThisWeek,
+    Later,
+}
+
+impl Urgency {
+    pub fn heading(&self) -> &'static str {
+        match self {
+            Urgency::Overdue => "OVERDUE",
+            Urgency::Today => "TODAY",
+            Urgency::ThisWeek => "THIS WEEK",
+            Urgency::Later => "LATER",
+        }
+    }
+}
+
+// A gradebook column, along with the current user's grade if one has been given
+pub type BBColumnGrade = (BBGradebookColumn, Option<BBGrade>);
 
 pub struct BBGradebookColumn {
     pub id: String,
     pub name: String,
     pub content_id: String,
     pub due: String, 
+    pub possible: Option<f64>,
 }
 
 impl BBGradebookColumn {
@@ -18,11 +46,36 @@ impl BBGradebookColumn {
                 name: member["name"].to_string(),
                 content_id: member["contentId"].to_string(),
                 due: member["grading"]["due"].to_string(), 
+                possible: member["score"]["possible"].as_f64(),
             }
         }).collect())
     }
 
+    // None for columns without a due date
+    pub fn urgency(&self) -> Option<Urgency> {
+        let days = local_days_from_today(&self.due)?;
+        if partial_cmp_dt(&self.due, &utc_now()) == Some(std::cmp::Ordering::Less) {
+            Some(Urgency::Overdue)
+        } else if days == 0 {
+            Some(Urgency::Today)
+        } else if days < 7 {
+            Some(Urgency::ThisWeek)
+        } else {
+            Some(Urgency::Later)
+        }
+    }
+
     pub fn view(&self) {
         println!("\"{}\": due {}", self.name, local_rfc2822(&self.due));
     }
-}
\ No newline at end of file
+
+    pub fn view_due(&self, course_alias: &str, submitted: bool) {
+        println!("[{}] \"{}\": due {} ({}){}",
+            course_alias,
+            self.name,
+            local_rfc2822(&self.due),
+            relative_to_now(&self.due),
+            if submitted { " [submitted]" } else { "" },
+        );
+    }
+}
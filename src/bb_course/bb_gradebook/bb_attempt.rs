@@ -0,0 +1,72 @@
+use crate::utils::time_utils::local_rfc2822;
+
+pub struct BBAttempt {
+    pub id: String,
+    pub status: String,
+    pub score: Option<f64>,
+    pub student_comments: String,
+    pub student_submission: String,
+    pub feedback: String,
+    pub created: String,
+    pub modified: String,
+}
+
+pub struct BBAttemptFile {
+    pub id: String,
+    pub name: String,
+}
+
+impl BBAttempt {
+    // Attempt statuses that mean the student has handed in
+    const SUBMITTED_STATUSES: [&'static str; 3] = [
+        "NeedsGrading",
+        "Completed",
+        "ReadyToPost",
+    ];
+
+    pub fn vec_from_json_results(json: Vec<u8>) -> Result<Vec<BBAttempt>, Box<dyn std::error::Error>> {
+        let json_string = std::string::String::from_utf8(json)?;
+        let parsed_json = json::parse(&json_string)?;
+
+        Ok(parsed_json["results"].members().map(|member| {
+            BBAttempt {
+                id: member["id"].to_string(),
+                status: member["status"].to_string(),
+                score: member["score"].as_f64(),
+                student_comments: member["studentComments"].as_str().unwrap_or("").to_string(),
+                student_submission: member["studentSubmission"].as_str().unwrap_or("").to_string(),
+                feedback: member["feedback"].as_str().unwrap_or("").to_string(),
+                created: member["created"].to_string(),
+                modified: member["modified"].to_string(),
+            }
+        }).collect())
+    }
+
+    pub fn is_submitted(&self) -> bool {
+        BBAttempt::SUBMITTED_STATUSES.contains(&self.status.as_str())
+    }
+
+    pub fn view(&self, column_name: &str, number: usize) {
+        println!("\"{}\": attempt {} ({}), created {}{}",
+            column_name,
+            number,
+            self.status,
+            local_rfc2822(&self.created),
+            self.score.map(|score| format!(", score {}", score)).unwrap_or_default(),
+        );
+    }
+}
+
+impl BBAttemptFile {
+    pub fn vec_from_json_results(json: Vec<u8>) -> Result<Vec<BBAttemptFile>, Box<dyn std::error::Error>> {
+        let json_string = std::string::String::from_utf8(json)?;
+        let parsed_json = json::parse(&json_string)?;
+
+        Ok(parsed_json["results"].members().map(|member| {
+            BBAttemptFile {
+                id: member["id"].to_string(),
+                name: member["name"].to_string(),
+            }
+        }).collect())
+    }
+}
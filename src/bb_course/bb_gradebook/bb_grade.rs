@@ -0,0 +1,53 @@
+use crate::utils::time_utils::local_rfc2822;
+
+pub struct BBGrade {
+    pub score: Option<f64>,
+    pub text: String,
+    pub status: String,
+    pub feedback: String,
+    pub last_graded: String,
+    pub exempt: bool,
+}
+
+impl BBGrade {
+    const VIEW_WIDTH: usize = 100;
+
+    pub fn from_json(json: Vec<u8>) -> Result<BBGrade, Box<dyn std::error::Error>> {
+        let json_string = std::string::String::from_utf8(json)?;
+        let parsed_json = json::parse(&json_string)?;
+
+        Ok(BBGrade {
+            score: parsed_json["score"].as_f64(),
+            text: parsed_json["displayGrade"]["text"].as_str().or_else(|| parsed_json["text"].as_str()).unwrap_or("").to_string(),
+            status: parsed_json["status"].to_string(),
+            feedback: parsed_json["feedback"].as_str().unwrap_or("").to_string(),
+            last_graded: parsed_json["lastRelevantDate"].to_string(),
+            exempt: parsed_json["exempt"].as_bool().unwrap_or(false),
+        })
+    }
+
+    pub fn display(&self, possible: Option<f64>) -> String {
+        match (self.score, possible) {
+            (Some(score), Some(possible)) => format!("{}/{}", score, possible),
+            (Some(score), None) => format!("{}", score),
+            (None, _) if !self.text.is_empty() => self.text.clone(),
+            (None, _) => String::from("-"),
+        }
+    }
+
+    pub fn view(&self, column_name: &str, possible: Option<f64>) {
+        let score = self.display(possible);
+        println!("\"{}\": {} ({}{}), graded {}",
+            column_name,
+            score,
+            self.status,
+            if self.exempt { ", exempt" } else { "" },
+            local_rfc2822(&self.last_graded),
+        );
+        if !self.feedback.is_empty() {
+            for line in html2text::from_read(self.feedback.as_bytes(), BBGrade::VIEW_WIDTH).lines() {
+                println!("    {}", line);
+            }
+        }
+    }
+}
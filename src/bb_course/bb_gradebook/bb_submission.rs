@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+use super::BBGradebookColumn;
+use super::bb_attempt::BBAttempt;
+use crate::bb_session::BBSession;
+use crate::bb_course::BBCourse;
+use crate::utils::fs_utils::path_size;
+use crate::utils::time_utils::{local_rfc2822, partial_cmp_dt, utc_now};
+
+// A new attempt on an assignment's gradebook column, which is only sent to the server by submit()
+pub struct BBSubmission<'a> {
+    pub course: &'a BBCourse,
+    pub column: BBGradebookColumn,
+    pub files: Vec<PathBuf>,
+    pub text: Option<String>,
+    pub draft: bool,
+}
+
+impl<'a> BBSubmission<'a> {
+    pub fn new(course: &'a BBCourse, column: BBGradebookColumn, files: &[PathBuf], text: Option<String>, draft: bool) -> Result<BBSubmission<'a>, Box<dyn std::error::Error>> {
+        if let Some(missing) = files.iter().find(|file| !file.is_file()) {
+            return Err(format!("File {:?} not found", missing).into());
+        }
+        if files.is_empty() && text.is_none() {
+            return Err("Nothing to submit; give files or a text".into());
+        }
+        Ok(BBSubmission {
+            course,
+            column,
+            files: files.to_vec(),
+            text,
+            draft,
+        })
+    }
+
+    pub fn preview(&self, previous_attempts: &[BBAttempt]) {
+        println!("COURSE: {}", self.course.alias);
+        println!("ASSIGNMENT: {}", self.column.name);
+        if self.column.due != "null" {
+            let past_due = partial_cmp_dt(&self.column.due, &utc_now()) == Some(std::cmp::Ordering::Less);
+            println!("DUE: {}{}", local_rfc2822(&self.column.due), if past_due { " (PAST DUE)" } else { "" });
+        }
+        println!("PREVIOUS ATTEMPTS: {}", previous_attempts.len());
+        println!("MODE: {}", if self.draft { "save as draft" } else { "submit for grading" });
+        for file in &self.files {
+            println!("FILE: {} ({:.1} kB)", file.display(), path_size(file) as f64/1000.0);
+        }
+        if let Some(text) = &self.text {
+            println!("TEXT: {}", text);
+        }
+    }
+
+    fn upload(&self, session: &BBSession, file: &std::path::Path) -> Result<String, Box<dyn std::error::Error>> {
+        let url = format!("https://{}/learn/api/public/v1/uploads", session.domain);
+        let json_string = String::from_utf8(session.upload_file(&url, file)?)?;
+        Ok(json::parse(&json_string)?["id"].to_string())
+    }
+
+    // Files are uploaded before the attempt is created, and the attempt is only submitted once all files are attached
+    pub fn submit(&self, session: &BBSession) -> Result<String, Box<dyn std::error::Error>> {
+        let mut upload_ids = Vec::new();
+        for file in &self.files {
+            println!("Uploading {:?}", file.file_name().unwrap());
+            upload_ids.push(self.upload(session, file)?);
+        }
+
+        let attempts_url = format!("https://{}/learn/api/public/v1/courses/{}/gradebook/columns/{}/attempts",
+            session.domain,
+            self.course.id,
+            self.column.id);
+        let mut attempt_json = json::object!{
+            status: "InProgress",
+        };
+        if let Some(text) = &self.text {
+            attempt_json["studentSubmission"] = text.clone().into();
+        }
+        let json_string = String::from_utf8(session.send_json("POST", &attempts_url, &attempt_json)?)?;
+        let attempt_id = json::parse(&json_string)?["id"].to_string();
+
+        let files_url = format!("https://{}/learn/api/public/v1/courses/{}/gradebook/attempts/{}/files",
+            session.domain,
+            self.course.id,
+            attempt_id);
+        // The attempt exists from here on, so errors name it, as it is left in progress on the server
+        for upload_id in upload_ids {
+            session.send_json("POST", &files_url, &json::object!{ uploadId: upload_id }).map_err(|err| {
+                format!("Attempt {} was created, but attaching the files failed, so it is left in progress: {}", attempt_id, err)
+            })?;
+        }
+
+        if !self.draft {
+            let attempt_url = format!("{}/{}", attempts_url, attempt_id);
+            session.send_json("PATCH", &attempt_url, &json::object!{ status: "NeedsGrading" }).map_err(|err| {
+                format!("Attempt {} was created with all files, but submitting it for grading failed, so it is left in progress: {}", attempt_id, err)
+            })?;
+        }
+        Ok(attempt_id)
+    }
+}
@@ -0,0 +1,35 @@
+pub struct BBGroup {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+}
+
+impl BBGroup {
+    pub fn vec_from_json_results(json: Vec<u8>) -> Result<Vec<BBGroup>, Box<dyn std::error::Error>> {
+        let json_string = std::string::String::from_utf8(json)?;
+        let parsed_json = json::parse(&json_string)?;
+
+        Ok(parsed_json["results"].members().map(|member| {
+            BBGroup {
+                id: member["id"].to_string(),
+                name: member["name"].to_string(),
+                description: member["description"].as_str().unwrap_or("").to_string(),
+            }
+        }).collect())
+    }
+
+    pub fn member_ids_from_json_results(json: Vec<u8>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let json_string = std::string::String::from_utf8(json)?;
+        let parsed_json = json::parse(&json_string)?;
+
+        Ok(parsed_json["results"].members().map(|member| member["userId"].to_string()).collect())
+    }
+
+    pub fn view(&self) {
+        if self.description.is_empty() {
+            println!("{}", self.name);
+        } else {
+            println!("{}: {}", self.name, html2text::from_read(self.description.as_bytes(), 100).trim());
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::path::{Path, PathBuf};
+use std::io::{Read, Write};
+use std::collections::BTreeMap;
+use crate::utils::fs_utils::{path_sha256, path_size, remove_path, copy_path};
+use crate::utils::time_utils::{utc_now, local_rfc2822, relative_to_now};
+
+// An earlier version of a synced file (or unzipped folder). Paths are relative to the course out_dir.
+#[derive(Debug, Clone)]
+pub struct BBVersion {
+    pub number: usize,
+    pub path: PathBuf,
+    pub saved: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl BBVersion {
+    pub fn view(&self) {
+        println!("  v{}: saved {} ({}), {:.1} kB, {}",
+            self.number,
+            local_rfc2822(&self.saved),
+            relative_to_now(&self.saved),
+            self.size as f64/1000.0,
+            self.path.display(),
+        );
+    }
+}
+
+// Earlier versions of files that syncs have replaced with different content, kept in the course out_dir
+pub struct BBHistory {
+    root: PathBuf,
+    pub versions: BTreeMap<PathBuf, Vec<BBVersion>>,
+}
+
+impl BBHistory {
+    const DIR: &'static str = ".bbcm-history";
+    const INDEX_FILENAME: &'static str = "history.json";
+    const PREVIOUS_SUFFIX: &'static str = ".bbcm-previous";
+
+    pub fn load(root: &Path) -> Result<BBHistory, Box<dyn std::error::Error>> {
+        let mut history = BBHistory {
+            root: root.to_path_buf(),
+            versions: BTreeMap::new(),
+        };
+        let path = root.join(BBHistory::DIR).join(BBHistory::INDEX_FILENAME);
+        if path.exists() {
+            let mut json_string = String::new();
+            std::fs::File::open(&path)?.read_to_string(&mut json_string)?;
+            for (file_path, members) in json::parse(&json_string)?.entries() {
+                let versions = members.members().map(|member| BBVersion {
+                    number: member["number"].as_usize().unwrap_or(0),
+                    path: PathBuf::from(member["path"].to_string()),
+                    saved: member["saved"].to_string(),
+                    size: member["size"].as_u64().unwrap_or(0),
+                    sha256: member["sha256"].to_string(),
+                }).collect();
+                history.versions.insert(PathBuf::from(file_path), versions);
+            }
+        }
+        Ok(history)
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut history_json = json::JsonValue::new_object();
+        for (path, versions) in &self.versions {
+            history_json[path.to_str().unwrap()] = versions.iter().map(|version| json::object!{
+                number: version.number,
+                path: version.path.to_str().unwrap(),
+                saved: version.saved.clone(),
+                size: version.size,
+                sha256: version.sha256.clone(),
+            }).collect::<Vec<json::JsonValue>>().into();
+        }
+        std::fs::create_dir_all(self.root.join(BBHistory::DIR))?;
+        let mut history_file = std::fs::File::create(self.root.join(BBHistory::DIR).join(BBHistory::INDEX_FILENAME))?;
+        history_file.write_all(history_json.pretty(4).as_bytes())?;
+        Ok(())
+    }
+
+    pub fn relative_path(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(&self.root).unwrap_or(path).to_path_buf()
+    }
+
+    // Keeps the current file (or folder) next to it before a sync replaces it.
+    // Files are hardlinked where possible, which is safe since downloads replace files instead of writing into them.
+    pub fn set_aside(path: &Path) -> Option<PathBuf> {
+        if !path.exists() {
+            return None;
+        }
+        let previous_path = path.with_file_name(format!("{}{}", path.file_name()?.to_string_lossy(), BBHistory::PREVIOUS_SUFFIX));
+        let _ = remove_path(&previous_path);
+        let set_aside = if path.is_dir() {
+            std::fs::rename(path, &previous_path)
+        } else {
+            std::fs::hard_link(path, &previous_path).or_else(|_| std::fs::copy(path, &previous_path).map(|_| ()))
+        };
+        set_aside.ok().map(|_| previous_path)
+    }
+
+    // The version that was set aside, if its replacement has different content. Otherwise it is removed again.
+    pub fn changed(previous_path: PathBuf, path: &Path) -> Option<PathBuf> {
+        if path_sha256(&previous_path).ok() != path_sha256(path).ok() {
+            Some(previous_path)
+        } else {
+            BBHistory::discard(&previous_path);
+            None
+        }
+    }
+
+    pub fn discard(previous_path: &Path) {
+        let _ = remove_path(previous_path);
+    }
+
+    // Moves a set aside version into the history dir, as <name>.v<number>.<extension> in the same folder structure
+    pub fn record(&mut self, path: &Path, previous_path: &Path) -> Result<&BBVersion, Box<dyn std::error::Error>> {
+        let relative_path = self.relative_path(path);
+        let versions = self.versions.entry(relative_path.clone()).or_default();
+        let number = versions.last().map(|version| version.number + 1).unwrap_or(1);
+        let file_name = relative_path.file_name().unwrap_or_default();
+        let version_name = match (relative_path.file_stem(), relative_path.extension()) {
+            (Some(stem), Some(extension)) if previous_path.is_file() => format!("{}.v{}.{}", stem.to_string_lossy(), number, extension.to_string_lossy()),
+            _ => format!("{}.v{}", file_name.to_string_lossy(), number),
+        };
+        let version_path = Path::new(BBHistory::DIR).join(relative_path.parent().unwrap_or_else(|| Path::new(""))).join(version_name);
+        std::fs::create_dir_all(self.root.join(&version_path).parent().unwrap())?;
+        std::fs::rename(previous_path, self.root.join(&version_path))?;
+        versions.push(BBVersion {
+            number,
+            size: path_size(&self.root.join(&version_path)),
+            sha256: path_sha256(&self.root.join(&version_path))?,
+            path: version_path,
+            saved: utc_now(),
+        });
+        Ok(versions.last().unwrap())
+    }
+
+    // Copies a version back in place. The current content is kept as a new version first, unless the history already has it.
+    pub fn restore(&mut self, path: &Path, number: usize) -> Result<(), Box<dyn std::error::Error>> {
+        let relative_path = self.relative_path(path);
+        let version = self.versions.get(&relative_path)
+            .and_then(|versions| versions.iter().find(|version| version.number == number))
+            .cloned()
+            .ok_or_else(|| format!("No version {} of {}", number, relative_path.display()))?;
+        let current_sha256 = path_sha256(path).ok();
+        let is_recorded = self.versions[&relative_path].iter().any(|version| Some(&version.sha256) == current_sha256.as_ref());
+        if current_sha256.is_some() && !is_recorded {
+            if let Some(previous_path) = BBHistory::set_aside(path) {
+                let version = self.record(path, &previous_path)?;
+                println!("Kept the current content as v{}.", version.number);
+            }
+        }
+        // Copied next to it and renamed, so that files linked to the content store aren't written into
+        let tmp_path = path.with_file_name(format!("{}.bbcm-restore", path.file_name().unwrap_or_default().to_string_lossy()));
+        let _ = remove_path(&tmp_path);
+        copy_path(&self.root.join(&version.path), &tmp_path)?;
+        if path.is_dir() {
+            std::fs::remove_dir_all(path)?;
+        }
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
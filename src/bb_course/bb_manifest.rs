@@ -0,0 +1,205 @@
+use std::path::{Path, PathBuf};
+use std::io::{Read, Write};
+use std::collections::{HashMap, HashSet, BTreeMap};
+use crate::utils::fs_utils::path_size;
+use crate::bb_session::BBValidators;
+
+// A content item as it was last seen in the course tree. Paths are relative to the course out_dir.
+#[derive(Debug, Clone)]
+pub struct BBManifestContent {
+    pub id: String,
+    pub parent_id: String,
+    pub title: String,
+    pub content_handler: String,
+    pub modified: String,
+    pub path: PathBuf,
+}
+
+// A file (or unzipped folder) written by bbcm, and where it came from
+#[derive(Debug, Clone)]
+pub struct BBManifestFile {
+    pub path: PathBuf,
+    pub content_id: String,
+    pub source_id: String, // Attachment id, or attempt file id for submissions
+    pub kind: String,
+    pub mimetype: String,
+    pub modified: String,
+    pub size: u64,
+    pub synced: String,
+    pub validators: BBValidators, // Of the last download response, for attachments
+}
+
+impl BBManifestFile {
+    pub const ATTACHMENT: &'static str = "attachment";
+    pub const LINK: &'static str = "link";
+    pub const SUBMISSION: &'static str = "submission";
+    pub const FEEDBACK: &'static str = "feedback";
+    pub const DISCUSSION: &'static str = "discussion";
+}
+
+// Record of everything synced into a course out_dir, kept in the out_dir itself
+pub struct BBManifest {
+    root: PathBuf,
+    pub contents: HashMap<String, BBManifestContent>,
+    pub files: BTreeMap<PathBuf, BBManifestFile>,
+    // Recorded or kept during the current sync, so that the rest can be pruned. Not saved.
+    seen_contents: HashSet<String>,
+    seen_files: HashSet<PathBuf>,
+}
+
+impl BBManifest {
+    const FILENAME: &'static str = ".bbcm_manifest.json";
+
+    pub fn load(root: &Path) -> Result<BBManifest, Box<dyn std::error::Error>> {
+        let mut manifest = BBManifest {
+            root: root.to_path_buf(),
+            contents: HashMap::new(),
+            files: BTreeMap::new(),
+            seen_contents: HashSet::new(),
+            seen_files: HashSet::new(),
+        };
+        let path = root.join(BBManifest::FILENAME);
+        if path.exists() {
+            let mut json_string = String::new();
+            std::fs::File::open(&path)?.read_to_string(&mut json_string)?;
+            let parsed_json = json::parse(&json_string)?;
+            for member in parsed_json["contents"].members() {
+                let content = BBManifestContent {
+                    id: member["id"].to_string(),
+                    parent_id: member["parent_id"].to_string(),
+                    title: member["title"].to_string(),
+                    content_handler: member["content_handler"].to_string(),
+                    modified: member["modified"].to_string(),
+                    path: PathBuf::from(member["path"].to_string()),
+                };
+                manifest.contents.insert(content.id.clone(), content);
+            }
+            for member in parsed_json["files"].members() {
+                let file = BBManifestFile {
+                    path: PathBuf::from(member["path"].to_string()),
+                    content_id: member["content_id"].to_string(),
+                    source_id: member["source_id"].to_string(),
+                    kind: member["kind"].to_string(),
+                    mimetype: member["mimetype"].to_string(),
+                    modified: member["modified"].to_string(),
+                    size: member["size"].as_u64().unwrap_or(0),
+                    synced: member["synced"].to_string(),
+                    validators: BBValidators {
+                        etag: member["etag"].as_str().unwrap_or_default().to_string(),
+                        last_modified: member["last_modified"].as_str().unwrap_or_default().to_string(),
+                    },
+                };
+                manifest.files.insert(file.path.clone(), file);
+            }
+        }
+        Ok(manifest)
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut contents: Vec<&BBManifestContent> = self.contents.values().collect();
+        contents.sort_by(|c1, c2| c1.path.cmp(&c2.path));
+        let manifest_json = json::object!{
+            contents: contents.into_iter().map(|content| json::object!{
+                id: content.id.clone(),
+                parent_id: content.parent_id.clone(),
+                title: content.title.clone(),
+                content_handler: content.content_handler.clone(),
+                modified: content.modified.clone(),
+                path: content.path.to_str().unwrap(),
+            }).collect::<Vec<json::JsonValue>>(),
+            files: self.files.values().map(|file| json::object!{
+                path: file.path.to_str().unwrap(),
+                content_id: file.content_id.clone(),
+                source_id: file.source_id.clone(),
+                kind: file.kind.clone(),
+                mimetype: file.mimetype.clone(),
+                modified: file.modified.clone(),
+                size: file.size,
+                synced: file.synced.clone(),
+                etag: file.validators.etag.clone(),
+                last_modified: file.validators.last_modified.clone(),
+            }).collect::<Vec<json::JsonValue>>(),
+        };
+        let mut manifest_file = std::fs::File::create(self.root.join(BBManifest::FILENAME))?;
+        manifest_file.write_all(manifest_json.pretty(4).as_bytes())?;
+        Ok(())
+    }
+
+    pub fn relative_path(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(&self.root).unwrap_or(path).to_path_buf()
+    }
+
+    pub fn absolute_path(&self, path: &Path) -> PathBuf {
+        self.root.join(path)
+    }
+
+    pub fn record_content(&mut self, mut content: BBManifestContent) {
+        content.path = self.relative_path(&content.path);
+        self.seen_contents.insert(content.id.clone());
+        self.contents.insert(content.id.clone(), content);
+    }
+
+    pub fn record_file(&mut self, mut file: BBManifestFile) {
+        file.path = self.relative_path(&file.path);
+        self.seen_files.insert(file.path.clone());
+        self.files.insert(file.path.clone(), file);
+    }
+
+    // For files that are still there, but weren't written again
+    pub fn keep_file(&mut self, path: &Path) {
+        let path = self.relative_path(path);
+        self.seen_files.insert(path);
+    }
+
+    pub fn keep_content_files(&mut self, content_id: &str) {
+        let paths: Vec<PathBuf> = self.files.values().filter(|file| file.content_id == content_id).map(|file| file.path.clone()).collect();
+        self.seen_files.extend(paths);
+    }
+
+    pub fn keep_source_files(&mut self, source_id: &str) {
+        let paths: Vec<PathBuf> = self.files.values().filter(|file| file.source_id == source_id).map(|file| file.path.clone()).collect();
+        self.seen_files.extend(paths);
+    }
+
+    // Everything below a folder that couldn't be synced
+    pub fn keep_below(&mut self, path: &Path) {
+        let path = self.relative_path(path);
+        let content_ids: Vec<String> = self.contents.values().filter(|content| content.path.starts_with(&path)).map(|content| content.id.clone()).collect();
+        let paths: Vec<PathBuf> = self.files.keys().filter(|file_path| file_path.starts_with(&path)).cloned().collect();
+        self.seen_contents.extend(content_ids);
+        self.seen_files.extend(paths);
+    }
+
+    // Removes the content items, and the files of the given kinds, that weren't seen during the current sync.
+    // The files themselves are left on disk. Returns the removed files.
+    pub fn prune(&mut self, kinds: &[&str], contents: bool) -> Vec<BBManifestFile> {
+        if contents {
+            let seen_contents = &self.seen_contents;
+            self.contents.retain(|id, _| seen_contents.contains(id));
+        }
+        let removed_paths: Vec<PathBuf> = self.files.values()
+            .filter(|file| kinds.contains(&file.kind.as_str()) && !self.seen_files.contains(&file.path))
+            .map(|file| file.path.clone())
+            .collect();
+        removed_paths.iter().filter_map(|path| self.files.remove(path)).collect()
+    }
+
+    pub fn content_dir(&self, content_id: &str) -> Option<PathBuf> {
+        self.contents.get(content_id).map(|content| self.absolute_path(&content.path))
+    }
+
+    // Attachments are only ever replaced as a whole, which makes them safe to link to the content store
+    pub fn attachment_paths(&self) -> Vec<PathBuf> {
+        self.files.values()
+            .filter(|file| file.kind == BBManifestFile::ATTACHMENT)
+            .map(|file| self.absolute_path(&file.path))
+            .collect()
+    }
+
+    // Sizes are read from disk once downloads have finished
+    pub fn refresh_sizes(&mut self) {
+        for file in self.files.values_mut() {
+            file.size = path_size(&self.root.join(&file.path));
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use crate::bb_course::bb_announcement::bb_user::BBUser;
+
+pub struct BBMembership {
+    pub user: BBUser,
+    pub role: String,
+    pub available: bool,
+}
+
+impl BBMembership {
+    // Short names accepted for the most common course roles
+    const ROLE_ALIASES: [(&'static str, &'static str); 3] = [
+        ("instructor", "Instructor"),
+        ("ta", "TeachingAssistant"),
+        ("student", "Student"),
+    ];
+
+    pub fn vec_from_json_results(json: Vec<u8>) -> Result<(Vec<BBMembership>, Option<String>), Box<dyn std::error::Error>> {
+        let json_string = std::string::String::from_utf8(json)?;
+        let parsed_json = json::parse(&json_string)?;
+
+        let memberships = parsed_json["results"].members().map(|member| {
+            BBMembership {
+                user: BBUser::from(&member["user"]),
+                role: member["courseRoleId"].to_string(),
+                available: member["availability"]["available"] != "No",
+            }
+        }).collect();
+        Ok((memberships, parsed_json["paging"]["nextPage"].as_str().map(String::from)))
+    }
+
+    pub fn role_id(role: &str) -> String {
+        BBMembership::ROLE_ALIASES.iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(role))
+            .map(|(_, id)| id.to_string())
+            .unwrap_or_else(|| role.to_string())
+    }
+
+    pub fn view(&self) {
+        println!("{:<20} {:<30} {}{}",
+            self.role,
+            self.user.name,
+            self.user.email,
+            if self.available { "" } else { " (unavailable)" },
+        );
+    }
+}
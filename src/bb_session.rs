@@ -1,45 +1,54 @@
+pub mod bb_response_cache;
 
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use curl::easy::{Easy, Handler, WriteError};
+use std::cell::RefCell;
+use std::sync::{Arc, OnceLock};
+use curl::easy::{Easy, Form, List};
+use bb_response_cache::BBResponseCache;
+use crate::utils::fs_utils::file_sha256;
 
 const PATH_LENGTH_WARNING_LIMIT: usize = 230;
 
-// https://docs.rs/curl/0.4.38/curl/easy/trait.Handler.html
-struct Collector(Vec<u8>);
-
-impl Handler for Collector {
-    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
-        self.0.extend_from_slice(data);
-        Ok(data.len())
-    }
-}
-
 #[derive(Debug, Clone)]
 pub struct BBSession {
     pub domain: String,
     pub cookie_jar_path: PathBuf,
+    pub cache: Option<BBResponseCache>,
+    pub offline: bool,
+    connected: Arc<OnceLock<bool>>, // Tested on the first request that needs the network, shared between clones
+}
+
+// Response validators of a downloaded file, sent back to skip it if it hasn't changed
+#[derive(Debug, Clone, Default)]
+pub struct BBValidators {
+    pub etag: String,
+    pub last_modified: String,
+}
+
+pub enum BBConditionalDownload {
+    Downloaded { size: f64, validators: BBValidators },
+    Unchanged, // The local copy was kept
 }
 
 impl BBSession {
-    pub fn new(domain: &str, cookie_jar_path: &Path) -> Result<BBSession, Box<dyn std::error::Error>> {
-        if !cookie_jar_path.exists() {
-            println!("Please export cookies from domain \"{}\" to following path: \n{}\nPress enter when done.", domain, cookie_jar_path.to_str().unwrap());
-            std::io::stdin().read_line(&mut String::new()).unwrap();
-        }
-        let bb_session = BBSession {
+    pub fn new(domain: &str, cookie_jar_path: &Path) -> BBSession {
+        BBSession {
             domain: domain.to_string(),
             cookie_jar_path: cookie_jar_path.to_path_buf(),
-        };
-        if bb_session.test_connection() {
-            Ok(bb_session)
-        } else {
-            Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, 
-                format!("Session connection test failed. Are cookies at {} up to date?", bb_session.cookie_jar_path.to_str().unwrap())
-            )))
+            cache: None,
+            offline: false,
+            connected: Arc::new(OnceLock::new()),
         }
     }
 
+    // Caches GET responses below cache_dir. Offline sessions only serve from the cache.
+    pub fn with_cache(mut self, cache_dir: &Path, offline: bool) -> BBSession {
+        self.cache = Some(BBResponseCache::new(cache_dir));
+        self.offline = offline;
+        self
+    }
+
     pub fn test_connection(&self) -> bool {
         if self.cookie_jar_path.exists() {
             let mut easy = Easy::new();
@@ -52,36 +61,167 @@ impl BBSession {
         }
     }
 
-    pub fn download_file(&self, url: &str, out_path: &Path) -> Result<f64, Box<dyn std::error::Error>> {
+    // Asks for cookies if there are none, and tests the connection once per run
+    fn ensure_connection(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.offline {
+            return Err(Box::new(std::io::Error::other("Not available in offline mode")));
+        }
+        let connected = self.connected.get_or_init(|| {
+            if !self.cookie_jar_path.exists() {
+                println!("Please export cookies from domain \"{}\" to following path: \n{}\nPress enter when done.", self.domain, self.cookie_jar_path.to_str().unwrap());
+                std::io::stdin().read_line(&mut String::new()).unwrap();
+            }
+            self.test_connection()
+        });
+        if *connected {
+            Ok(())
+        } else {
+            Err(Box::new(std::io::Error::other(
+                format!("Session connection test failed. Are cookies at {} up to date?", self.cookie_jar_path.to_str().unwrap())
+            )))
+        }
+    }
+
+    // Downloads to out_path. Sends the validators of the local copy, and keeps it when the server reports the file as not modified,
+    // or when the size and SHA-256 digest announced by the server match it. Downloads go to a .part file that replaces out_path when complete.
+    pub fn download_file_if_changed(&self, url: &str, out_path: &Path, validators: Option<&BBValidators>) -> Result<BBConditionalDownload, Box<dyn std::error::Error>> {
+        self.ensure_connection()?;
 
         if let Ok(absolute_path) = out_path.canonicalize() {
             if absolute_path.to_str().unwrap().len() > PATH_LENGTH_WARNING_LIMIT {
                 eprintln!("WARNING: Path length exceeds {} characters, and might approach system limit.", PATH_LENGTH_WARNING_LIMIT);
             }
-        } 
-        
-        let mut out_file = std::fs::File::create(out_path).expect("Error creating out file");
+        }
+
+        let local_size = std::fs::metadata(out_path).ok().filter(|metadata| metadata.is_file()).map(|metadata| metadata.len());
+        let part_path = out_path.with_file_name(format!("{}.part", out_path.file_name().unwrap().to_string_lossy()));
+        let response_headers = RefCell::new(Vec::new());
+        let mut part_file: Option<std::fs::File> = None;
+        let mut unchanged = false;
+        let mut write_error = None;
 
         let mut easy = Easy::new();
         easy.url(url)?;
-        easy.write_function(move |data| { 
-            out_file.write_all(data).expect("Error writing data");
-            Ok(data.len())
-        })?;
-
         easy.cookie_file(&self.cookie_jar_path).unwrap();
-
         easy.follow_location(true)?; //Viktig fordi BB redirecter (302)
         easy.fail_on_error(true)?; //Viktig for å faile på 401
-        
-        easy.perform()?;
-        
-        Ok(easy.download_size()?)
+
+        if let Some(validators) = validators {
+            let mut headers = List::new();
+            if !validators.etag.is_empty() {
+                headers.append(&format!("If-None-Match: {}", validators.etag))?;
+            }
+            if !validators.last_modified.is_empty() {
+                headers.append(&format!("If-Modified-Since: {}", validators.last_modified))?;
+            }
+            easy.http_headers(headers)?;
+        }
+
+        let result = {
+            let mut transfer = easy.transfer();
+            transfer.header_function(|header| {
+                let header = String::from_utf8_lossy(header).trim().to_string();
+                if header.starts_with("HTTP/") {
+                    response_headers.borrow_mut().clear();
+                }
+                response_headers.borrow_mut().push(header);
+                true
+            })?;
+            transfer.write_function(|data| {
+                if part_file.is_none() {
+                    // Decided once the headers are in, before any of the body is written. Returning 0 aborts the transfer.
+                    if BBSession::matches_local_copy(&response_headers.borrow(), out_path, local_size) {
+                        unchanged = true;
+                        return Ok(0);
+                    }
+                    match std::fs::File::create(&part_path) {
+                        Ok(file) => part_file = Some(file),
+                        Err(err) => {
+                            write_error = Some(err);
+                            return Ok(0);
+                        },
+                    }
+                }
+                if let Err(err) = part_file.as_mut().unwrap().write_all(data) {
+                    write_error = Some(err);
+                    return Ok(0);
+                }
+                Ok(data.len())
+            })?;
+            transfer.perform()
+        };
+        drop(part_file);
+
+        if unchanged {
+            return Ok(BBConditionalDownload::Unchanged);
+        }
+        if let Err(err) = result {
+            let _ = std::fs::remove_file(&part_path);
+            return Err(match write_error {
+                Some(write_error) => Box::new(write_error),
+                None => Box::new(err),
+            });
+        }
+        if easy.response_code()? == 304 {
+            return Ok(BBConditionalDownload::Unchanged);
+        }
+        // Empty files never reach the write function. The local copy is always replaced, never written into,
+        // as it may be a link to the content store.
+        if !part_path.exists() {
+            std::fs::File::create(&part_path)?;
+        }
+        std::fs::rename(&part_path, out_path)?;
+        let headers = response_headers.into_inner();
+        Ok(BBConditionalDownload::Downloaded {
+            size: easy.download_size()?,
+            validators: BBValidators {
+                etag: BBSession::header_value(&headers, "ETag").unwrap_or_default().to_string(),
+                last_modified: BBSession::header_value(&headers, "Last-Modified").unwrap_or_default().to_string(),
+            },
+        })
+    }
+
+    // The server has to announce both the size and a SHA-256 digest of the file
+    fn matches_local_copy(headers: &[String], local_path: &Path, local_size: Option<u64>) -> bool {
+        let server_size = BBSession::header_value(headers, "Content-Length").and_then(|value| value.parse::<u64>().ok());
+        match (local_size, server_size, BBSession::server_sha256(headers)) {
+            (Some(local_size), Some(server_size), Some(server_sha256)) if local_size == server_size => {
+                file_sha256(local_path).map(|local_sha256| local_sha256 == server_sha256).unwrap_or(false)
+            },
+            _ => false,
+        }
+    }
+
+    // Hex encoded SHA-256 from a Digest, Repr-Digest or Content-Digest header, or from an ETag that is a SHA-256 itself
+    fn server_sha256(headers: &[String]) -> Option<String> {
+        for name in &["Repr-Digest", "Content-Digest", "Digest"] {
+            let digest = BBSession::header_value(headers, name).and_then(|value| {
+                value.split(',')
+                    .filter_map(|entry| entry.trim().split_once('='))
+                    .find(|(algorithm, _)| algorithm.eq_ignore_ascii_case("sha-256"))
+                    .and_then(|(_, encoded)| base64::decode(encoded.trim_matches(':')).ok())
+            });
+            if let Some(digest) = digest.filter(|digest| digest.len() == 32) {
+                return Some(digest.iter().map(|byte| format!("{:02x}", byte)).collect());
+            }
+        }
+        BBSession::header_value(headers, "ETag")
+            .map(|etag| etag.trim_start_matches("W/").trim_matches('"').to_lowercase())
+            .filter(|etag| etag.len() == 64 && etag.chars().all(|c| c.is_ascii_hexdigit()))
     }
 
+    // Served from the response cache when offline, or when the server reports the cached response as current
     pub fn download_bytes(&self, url: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let cached = self.cache.as_ref().and_then(|cache| cache.get(url));
+        if self.offline {
+            return cached.map(|cached| cached.body).ok_or_else(|| {
+                Box::new(std::io::Error::other(format!("Not cached, and offline: {}", url))) as Box<dyn std::error::Error>
+            });
+        }
+        self.ensure_connection()?;
 
         let mut buf = Vec::new();
+        let mut response_headers = Vec::new();
         let mut easy = Easy::new();
 
         easy.url(url)?;
@@ -91,18 +231,114 @@ impl BBSession {
         easy.follow_location(true)?; //Viktig fordi BB redirecter (302)
         easy.fail_on_error(true)?; //Viktig for å faile på 401
 
+        if let Some(cached) = &cached {
+            let mut headers = List::new();
+            if let Some(etag) = &cached.etag {
+                headers.append(&format!("If-None-Match: {}", etag))?;
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                headers.append(&format!("If-Modified-Since: {}", last_modified))?;
+            }
+            easy.http_headers(headers)?;
+        }
+
         { // Scope to make transfer drop borrow of buf
             let mut transfer = easy.transfer();
+            transfer.header_function(|header| {
+                let header = String::from_utf8_lossy(header).trim().to_string();
+                // Only the headers of the last response after redirects are kept
+                if header.starts_with("HTTP/") {
+                    response_headers.clear();
+                }
+                response_headers.push(header);
+                true
+            })?;
             transfer.write_function(|data| { 
                 buf.extend_from_slice(data);
                 Ok(data.len())
             })?;
             transfer.perform()?;
         }
+
+        if let (304, Some(cached)) = (easy.response_code()?, cached) {
+            return Ok(cached.body);
+        }
+        if let Some(cache) = &self.cache {
+            let etag = BBSession::header_value(&response_headers, "ETag");
+            let last_modified = BBSession::header_value(&response_headers, "Last-Modified");
+            if let Err(err) = cache.put(url, etag, last_modified, &buf) {
+                eprintln!("Error caching response for {}: {}", url, err);
+            }
+        }
         
         Ok(buf)
     }
 
+    fn header_value<'a>(headers: &'a [String], name: &str) -> Option<&'a str> {
+        headers.iter()
+            .filter_map(|header| header.split_once(':'))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim())
+    }
+
+    // Sends a request with a JSON body (POST, PATCH) and returns the response body
+    pub fn send_json(&self, method: &str, url: &str, body: &json::JsonValue) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        self.ensure_connection()?;
+
+        let mut buf = Vec::new();
+        let mut easy = Easy::new();
+
+        easy.url(url)?;
+        easy.cookie_file(&self.cookie_jar_path).unwrap();
+        easy.custom_request(method)?;
+
+        let mut headers = List::new();
+        headers.append("Content-Type: application/json")?;
+        easy.http_headers(headers)?;
+        easy.post_fields_copy(body.dump().as_bytes())?;
+
+        easy.fail_on_error(true)?;
+
+        {
+            let mut transfer = easy.transfer();
+            transfer.write_function(|data| { 
+                buf.extend_from_slice(data);
+                Ok(data.len())
+            })?;
+            transfer.perform()?;
+        }
+
+        Ok(buf)
+    }
+
+    // Uploads a file as multipart form data and returns the response body
+    pub fn upload_file(&self, url: &str, path: &Path) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        self.ensure_connection()?;
+
+        let mut buf = Vec::new();
+        let mut easy = Easy::new();
+
+        easy.url(url)?;
+        easy.cookie_file(&self.cookie_jar_path).unwrap();
+
+        let mut form = Form::new();
+        form.part("file").file(path).add()?;
+        easy.httppost(form)?;
+
+        easy.fail_on_error(true)?;
+
+        {
+            let mut transfer = easy.transfer();
+            transfer.write_function(|data| { 
+                buf.extend_from_slice(data);
+                Ok(data.len())
+            })?;
+            transfer.perform()?;
+        }
+
+        Ok(buf)
+    }
+
     pub fn download_courses_json(&self, query_parameters: &[&str]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
         let mut url = format!("https://{}/learn/api/public/v3/courses",
             self.domain);
@@ -130,6 +366,6 @@ impl std::convert::From<json::JsonValue> for BBSession {
         BBSession::new(
             &course["domain"].to_string(), 
             Path::new(&course["cookie_jar_path"].to_string())
-        ).expect("Error creating new BBSession from JSON")
+        )
     }
 }
\ No newline at end of file
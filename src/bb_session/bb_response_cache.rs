@@ -0,0 +1,81 @@
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::utils::time_utils::utc_now;
+
+pub struct BBCachedResponse {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub body: Vec<u8>,
+}
+
+// Response bodies of GET requests, keyed by URL. Every entry is a metadata file with the validators, and a body file.
+#[derive(Debug, Clone)]
+pub struct BBResponseCache {
+    dir: PathBuf,
+}
+
+impl BBResponseCache {
+    pub fn new(dir: &Path) -> BBResponseCache {
+        BBResponseCache {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    // FNV-1a, which unlike the std hasher is stable between releases
+    fn key(url: &str) -> String {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in url.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        format!("{:016x}", hash)
+    }
+
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let key = BBResponseCache::key(url);
+        (self.dir.join(format!("{}.json", key)), self.dir.join(format!("{}.body", key)))
+    }
+
+    pub fn get(&self, url: &str) -> Option<BBCachedResponse> {
+        let (meta_path, body_path) = self.paths(url);
+        let mut json_string = String::new();
+        std::fs::File::open(meta_path).ok()?.read_to_string(&mut json_string).ok()?;
+        let meta = json::parse(&json_string).ok()?;
+        // Colliding keys are treated as misses
+        if meta["url"].as_str() != Some(url) {
+            return None;
+        }
+        let mut body = Vec::new();
+        std::fs::File::open(body_path).ok()?.read_to_end(&mut body).ok()?;
+        Some(BBCachedResponse {
+            etag: meta["etag"].as_str().map(str::to_string),
+            last_modified: meta["last_modified"].as_str().map(str::to_string),
+            body,
+        })
+    }
+
+    // The body is written first, so that a metadata file always has a complete body
+    pub fn put(&self, url: &str, etag: Option<&str>, last_modified: Option<&str>, body: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(&self.dir)?;
+        let (meta_path, body_path) = self.paths(url);
+        let meta = json::object!{
+            url: url,
+            etag: etag,
+            last_modified: last_modified,
+            fetched: utc_now(),
+        };
+        BBResponseCache::write_atomic(&body_path, body)?;
+        BBResponseCache::write_atomic(&meta_path, meta.dump().as_bytes())?;
+        Ok(())
+    }
+
+    // Responses are cached from several download threads at once
+    fn write_atomic(path: &Path, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        static WRITES: AtomicUsize = AtomicUsize::new(0);
+        let tmp_path = path.with_extension(format!("tmp{}-{}", std::process::id(), WRITES.fetch_add(1, Ordering::Relaxed)));
+        std::fs::File::create(&tmp_path)?.write_all(data)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+use crate::bb_session::BBSession;
+use crate::utils::time_utils::{partial_cmp_dt, utc_now, local_rfc2822};
+use std::cmp::Ordering;
+
+pub struct BBTerm {
+    pub id: String,
+    pub external_id: String,
+    pub name: String,
+    pub start: String, // Only set for terms with a date range
+    pub end: String,
+}
+
+impl BBTerm {
+    pub fn vec_from_json_results(json: Vec<u8>) -> Result<(Vec<BBTerm>, Option<String>), Box<dyn std::error::Error>> {
+        let json_string = std::string::String::from_utf8(json)?;
+        let parsed_json = json::parse(&json_string)?;
+
+        let terms = parsed_json["results"].members().map(|member| {
+            let duration = &member["availability"]["duration"];
+            let is_date_range = duration["type"] == "DateRange";
+            BBTerm {
+                id: member["id"].to_string(),
+                external_id: member["externalId"].as_str().unwrap_or("").to_string(),
+                name: member["name"].to_string(),
+                start: if is_date_range { duration["start"].as_str().unwrap_or("").to_string() } else { String::new() },
+                end: if is_date_range { duration["end"].as_str().unwrap_or("").to_string() } else { String::new() },
+            }
+        }).collect();
+        Ok((terms, parsed_json["paging"]["nextPage"].as_str().map(String::from)))
+    }
+
+    pub fn get_all(session: &BBSession) -> Result<Vec<BBTerm>, Box<dyn std::error::Error>> {
+        let mut terms = Vec::new();
+        let mut url = format!("https://{}/learn/api/public/v1/terms", session.domain);
+        loop {
+            let (page, next_page) = BBTerm::vec_from_json_results(session.download_bytes(&url)?)?;
+            terms.extend(page);
+            match next_page {
+                Some(next_page) => url = format!("https://{}{}", session.domain, next_page),
+                None => break,
+            }
+        }
+        terms.sort_by(|t1, t2| partial_cmp_dt(&t1.start, &t2.start).unwrap_or(Ordering::Equal));
+        Ok(terms)
+    }
+
+    // Terms without a date range are never current
+    pub fn is_current(&self) -> bool {
+        let now = utc_now();
+        partial_cmp_dt(&self.start, &now) == Some(Ordering::Less) && partial_cmp_dt(&self.end, &now) == Some(Ordering::Greater)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.end.is_empty() && partial_cmp_dt(&self.end, &utc_now()) == Some(Ordering::Less)
+    }
+
+    // Accepts "current", or a term id, external id or name
+    pub fn find<'a>(terms: &'a [BBTerm], query: &str) -> Vec<&'a BBTerm> {
+        if query == "current" {
+            terms.iter().filter(|term| term.is_current()).collect()
+        } else {
+            terms.iter().filter(|term| {
+                term.id == query || term.external_id.eq_ignore_ascii_case(query) || term.name.eq_ignore_ascii_case(query)
+            }).collect()
+        }
+    }
+
+    // Used for the semester part of new course registrations
+    pub fn semester(&self) -> &str {
+        if self.external_id.is_empty() { &self.name } else { &self.external_id }
+    }
+
+    pub fn heading(&self) -> String {
+        if self.start.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} ({} - {})", self.name, local_rfc2822(&self.start), local_rfc2822(&self.end))
+        }
+    }
+}
@@ -1,12 +1,61 @@
 use structopt::StructOpt;
+use structopt::clap::AppSettings;
+use std::path::PathBuf;
+use chrono::Duration;
+use crate::utils::time_utils::{utc_from_date_arg, parse_duration};
+use crate::export::announcement_feed::FeedFormat;
+use crate::export::announcement_mail::MailFormat;
+use crate::export::discussion_document::DocumentFormat;
+use crate::hooks::HookTrigger;
 #[derive(StructOpt, Debug)]
 #[structopt(name = "Blackboard Course Manager", about = "A tool for managing Blackboard courses")]
+pub struct BbcmArgs {
+    #[structopt(
+        long,
+        global=true,
+        help="Only use cached responses, without contacting Blackboard",
+    )]
+    pub offline: bool,
+
+    #[structopt(subcommand)]
+    pub command: Bbcm,
+}
+
+#[derive(StructOpt, Debug)]
 pub enum Bbcm {
     #[structopt(about="Register new course")]
-    Register,
+    Register {
+        #[structopt(
+            short,
+            long,
+            help="Term of the course: \"current\", or a term id, external id or name (default: BBCM_SEMESTER)",
+        )]
+        term: Option<String>,
+    },
 
-    #[structopt(about="View registered courses")]
-    Courses,
+    #[structopt(about="View registered courses, grouped by term")]
+    Courses {
+        #[structopt(
+            short,
+            long,
+            help="Only show courses of a term: \"current\", or a term id, external id or name",
+        )]
+        term: Option<String>,
+
+        #[structopt(
+            short,
+            long,
+            help="Show course details, sync state, unread announcements and the next deadline",
+        )]
+        long: bool,
+
+        #[structopt(
+            short,
+            long,
+            help="Fetch course details again",
+        )]
+        refresh: bool,
+    },
 
     #[structopt(about="Download course file tree")]
     Tree {
@@ -26,6 +75,13 @@ pub enum Bbcm {
 
     #[structopt(about="Download course file trees for all registered courses")]
     Trees {
+        #[structopt(
+            short,
+            long,
+            help="Only download trees for courses of a term: \"current\", or a term id, external id or name",
+        )]
+        term: Option<String>,
+
         #[structopt(
             short,
             long,
@@ -34,13 +90,17 @@ pub enum Bbcm {
         overwrite: bool,
     },
 
-    #[structopt(about="View course announcements")]
+    #[structopt(
+        about="View course announcements",
+        settings=&[AppSettings::SubcommandsNegateReqs, AppSettings::ArgsNegateSubcommands],
+    )]
     Announcements {
         #[structopt(
             name="course-alias",
             help="Alias of course",
+            required_unless="new",
         )]
-        course_alias: String,
+        course_alias: Option<String>,
 
         #[structopt(
             short,
@@ -57,6 +117,96 @@ pub enum Bbcm {
             help="Offset announcements",
         )]
         offset: Option<usize>,
+
+        #[structopt(
+            short,
+            long,
+            help="Only show unseen or edited announcements, for all registered courses unless an alias is given",
+        )]
+        new: bool,
+
+        #[structopt(subcommand)]
+        command: Option<AnnouncementsCommand>,
+    },
+
+    #[structopt(about="View announcements from several courses, sorted by creation time")]
+    Feed {
+        #[structopt(
+            name="course-aliases",
+            help="Aliases of courses to include (default: all registered courses)",
+        )]
+        course_aliases: Vec<String>,
+
+        #[structopt(
+            long,
+            help="Only show announcements created at or after this date (format: 2021-08-13)",
+            parse(try_from_str = utc_from_date_arg),
+        )]
+        since: Option<String>,
+
+        #[structopt(
+            long,
+            help="Only show announcements created before this date (format: 2021-08-13)",
+            parse(try_from_str = utc_from_date_arg),
+        )]
+        until: Option<String>,
+
+        #[structopt(
+            short,
+            long,
+            help="Only show announcements with title or body containing this text",
+        )]
+        search: Option<String>,
+    },
+
+    #[structopt(about="Export course announcements as Atom or RSS feeds, per course and combined")]
+    ExportFeeds {
+        #[structopt(
+            name="course-aliases",
+            help="Aliases of courses to export (default: all registered courses)",
+        )]
+        course_aliases: Vec<String>,
+
+        #[structopt(
+            short,
+            long,
+            default_value="atom",
+            possible_values=&["atom", "rss"],
+            help="Feed format",
+        )]
+        format: FeedFormat,
+
+        #[structopt(
+            long,
+            parse(from_os_str),
+            help="Directory to write feeds to (default: feeds folder in output directory)",
+        )]
+        feed_dir: Option<PathBuf>,
+    },
+
+    #[structopt(about="Export course announcements as email messages to a Maildir or mbox")]
+    ExportMail {
+        #[structopt(
+            name="course-aliases",
+            help="Aliases of courses to export (default: all registered courses)",
+        )]
+        course_aliases: Vec<String>,
+
+        #[structopt(
+            short,
+            long,
+            default_value="maildir",
+            possible_values=&["maildir", "mbox"],
+            help="Mailbox format",
+        )]
+        format: MailFormat,
+
+        #[structopt(
+            long,
+            parse(from_os_str),
+            help="Maildir or mbox to write to (default: mail folder or announcements.mbox in output directory)",
+        )]
+        mail_path: Option<PathBuf>,
     },
 
     #[structopt(about="View gradebook columns for all registered courses")]
@@ -69,6 +219,311 @@ pub enum Bbcm {
         past: bool,
     },
 
+    #[structopt(about="View upcoming deadlines for all registered courses")]
+    Due {
+        #[structopt(
+            short,
+            long,
+            parse(try_from_str = parse_duration),
+            help="Only show deadlines within this time from now, and overdue ones as far back (format: 12h, 7d, 2w)",
+        )]
+        within: Option<Duration>,
+    },
+
+    #[structopt(about="Download my submitted attempts and instructor feedback")]
+    Submissions {
+        #[structopt(
+            name="course-alias",
+            help="Alias of course (default: all registered courses)",
+        )]
+        course_alias: Option<String>,
+
+        #[structopt(
+            short,
+            long,
+            help="Only list attempts",
+        )]
+        list: bool,
+    },
+
+    #[structopt(about="Hand in files for an assignment")]
+    Submit {
+        #[structopt(
+            name="course-alias",
+            help="Alias of course",
+        )]
+        course_alias: String,
+
+        #[structopt(
+            help="Assignment title or gradebook column name",
+        )]
+        assignment: String,
+
+        #[structopt(
+            parse(from_os_str),
+            help="Files to hand in",
+        )]
+        files: Vec<PathBuf>,
+
+        #[structopt(
+            short,
+            long,
+            help="Text submission to include",
+        )]
+        text: Option<String>,
+
+        #[structopt(
+            long,
+            help="Save the attempt as a draft instead of submitting it for grading",
+        )]
+        draft: bool,
+
+        #[structopt(
+            long,
+            help="Only show what would be submitted",
+        )]
+        dry_run: bool,
+    },
+
+    #[structopt(about="View my grades and feedback")]
+    Grades {
+        #[structopt(
+            name="course-alias",
+            help="Alias of course (default: all registered courses)",
+        )]
+        course_alias: Option<String>,
+    },
+
+    #[structopt(about="Export deadlines and calendar items to an iCalendar file")]
+    Ics {
+        #[structopt(
+            name="course-aliases",
+            help="Aliases of courses to export (default: all registered courses)",
+        )]
+        course_aliases: Vec<String>,
+
+        #[structopt(
+            short,
+            long,
+            parse(try_from_str = parse_duration),
+            help="Add a reminder this long before each event (format: 30m, 2h, 1d). Can be repeated",
+        )]
+        alarm: Vec<Duration>,
+
+        #[structopt(
+            long,
+            parse(from_os_str),
+            help="File to write calendar to (default: bbcm.ics in output directory)",
+        )]
+        ics_path: Option<PathBuf>,
+    },
+
+    #[structopt(about="View course members and staff")]
+    People {
+        #[structopt(
+            name="course-alias",
+            help="Alias of course",
+        )]
+        course_alias: String,
+
+        #[structopt(
+            short,
+            long,
+            help="Only show members with this role (instructor, ta, student or a Blackboard role id). Can be repeated",
+        )]
+        role: Vec<String>,
+
+        #[structopt(
+            long,
+            parse(from_os_str),
+            help="Export members to this CSV file",
+        )]
+        csv: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            parse(from_os_str),
+            help="Export members to this vCard file",
+        )]
+        vcard: Option<PathBuf>,
+    },
+
+    #[structopt(about="View my course groups")]
+    Groups {
+        #[structopt(
+            name="course-alias",
+            help="Alias of course",
+        )]
+        course_alias: String,
+
+        #[structopt(
+            short,
+            long,
+            help="Show all groups, not only the ones I'm a member of",
+        )]
+        all: bool,
+
+        #[structopt(
+            short,
+            long,
+            help="Show group members",
+        )]
+        members: bool,
+    },
+
+    #[structopt(about="Export course discussion threads as documents")]
+    Discussions {
+        #[structopt(
+            name="course-alias",
+            help="Alias of course (default: all registered courses)",
+        )]
+        course_alias: Option<String>,
+
+        #[structopt(
+            short,
+            long,
+            default_value="markdown",
+            possible_values=&["markdown", "html"],
+            help="Document format",
+        )]
+        format: DocumentFormat,
+    },
+
+    #[structopt(about="Keep syncing all registered courses, writing changes to stdout as JSON lines")]
+    Watch {
+        #[structopt(
+            short,
+            long,
+            default_value="30m",
+            parse(try_from_str=parse_duration),
+            help="Time between sync rounds (format: 30m, 2h)",
+        )]
+        interval: Duration,
+
+        #[structopt(
+            long="hook",
+            name="command",
+            number_of_values=1,
+            help="Shell command to run for every change, with the JSON event on stdin (can be repeated)",
+        )]
+        hooks: Vec<String>,
+
+        #[structopt(
+            short,
+            long,
+            help="Show desktop notifications for new files, announcements and grades (requires the \"notifications\" feature)",
+        )]
+        notify: bool,
+    },
+
+    #[structopt(about="Update the full-text search index from synced files and cached announcements")]
+    Index {
+        #[structopt(
+            name="course-alias",
+            help="Alias of course (default: all registered courses)",
+        )]
+        course_alias: Option<String>,
+
+        #[structopt(
+            short,
+            long,
+            help="Extract the text of all files again",
+        )]
+        rebuild: bool,
+    },
+
+    #[structopt(about="Search synced course material and announcements")]
+    Search {
+        #[structopt(
+            name="query",
+            required=true,
+            help="Search terms",
+        )]
+        query: Vec<String>,
+
+        #[structopt(
+            short,
+            long,
+            name="course-alias",
+            help="Only search this course",
+        )]
+        course: Option<String>,
+
+        #[structopt(
+            short,
+            long,
+            default_value="10",
+            help="Maximum number of results",
+        )]
+        limit: usize,
+    },
+
+    #[structopt(about="Browse synced courses, announcements, deadlines and search in a local web UI")]
+    Serve {
+        #[structopt(
+            short,
+            long,
+            default_value="8080",
+            help="Port to listen on (on localhost only)",
+        )]
+        port: u16,
+    },
+
+    #[structopt(about="Manage commands run after downloaded files, course syncs and new announcements")]
+    Hooks {
+        #[structopt(subcommand)]
+        command: HooksCommand,
+    },
+
+    #[structopt(about="Move the folders of all courses of a finished term to the archive, and remove the courses")]
+    Archive {
+        #[structopt(
+            name="term",
+            help="Term id, external id or name",
+        )]
+        term: String,
+
+        #[structopt(
+            short,
+            long,
+            help="Archive the term even if it hasn't ended yet",
+        )]
+        force: bool,
+    },
+
+    #[structopt(about="Link identical synced files to the content store, and report the space reclaimed")]
+    Dedupe {
+        #[structopt(
+            name="course-alias",
+            help="Alias of course (default: all registered courses)",
+        )]
+        course_alias: Option<String>,
+
+        #[structopt(
+            short,
+            long,
+            help="Only report what would be reclaimed",
+        )]
+        dry_run: bool,
+    },
+
+    #[structopt(about="List earlier versions of a synced file, or restore one")]
+    History {
+        #[structopt(
+            name="path",
+            parse(from_os_str),
+            help="Path of a synced file or unzipped folder",
+        )]
+        path: PathBuf,
+
+        #[structopt(
+            short,
+            long,
+            help="Version to restore, the current content is kept as a new version",
+        )]
+        restore: Option<usize>,
+    },
+
     #[structopt(about="Remove registered course")]
     Remove {
         #[structopt(
@@ -81,3 +536,64 @@ pub enum Bbcm {
     #[structopt(about="Remove all registered courses")]
     Reset
 }
+
+
+#[derive(StructOpt, Debug)]
+pub enum AnnouncementsCommand {
+    #[structopt(about="Mark announcements that have been shown as read, for all registered courses unless an alias is given")]
+    MarkRead {
+        #[structopt(
+            name="course-alias",
+            help="Alias of course",
+        )]
+        course_alias: Option<String>,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+pub enum HooksCommand {
+    #[structopt(about="List configured hooks")]
+    List,
+
+    #[structopt(about="Add a hook, run with event details as BBCM_* environment variables and as JSON on stdin")]
+    Add {
+        #[structopt(
+            name="trigger",
+            possible_values=&["file", "course", "announcement"],
+            help="Run after each downloaded file, after each course sync, or for each new announcement",
+        )]
+        trigger: HookTrigger,
+
+        #[structopt(
+            name="command",
+            help="Shell command to run",
+        )]
+        command: String,
+
+        #[structopt(
+            short,
+            long,
+            name="course-alias",
+            help="Only run for this course (default: all courses)",
+        )]
+        course: Option<String>,
+
+        #[structopt(
+            short,
+            long,
+            default_value="60s",
+            parse(try_from_str=parse_duration),
+            help="Time before the command is stopped (format: 30s, 5m)",
+        )]
+        timeout: Duration,
+    },
+
+    #[structopt(about="Remove a hook")]
+    Remove {
+        #[structopt(
+            name="index",
+            help="Index of the hook, as shown by hooks list",
+        )]
+        index: usize,
+    },
+}
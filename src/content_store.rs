@@ -0,0 +1,276 @@
+use std::path::{Path, PathBuf};
+use std::collections::{HashMap, HashSet};
+use crate::utils::fs_utils::file_sha256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkMode {
+    Hardlink,
+    Symlink,
+    Copy,
+}
+
+impl LinkMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkMode::Hardlink => "hardlink",
+            LinkMode::Symlink => "symlink",
+            LinkMode::Copy => "copy",
+        }
+    }
+}
+
+impl std::str::FromStr for LinkMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LinkMode, String> {
+        match s {
+            "hardlink" => Ok(LinkMode::Hardlink),
+            "symlink" => Ok(LinkMode::Symlink),
+            "copy" => Ok(LinkMode::Copy),
+            _ => Err(format!("Unknown store mode \"{}\" (expected hardlink, symlink or copy)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct StoreReport {
+    pub files: usize,
+    pub duplicates: usize,
+    pub reclaimed: u64, // Only counted for links, as copies may or may not share blocks
+}
+
+impl StoreReport {
+    pub fn merge(&mut self, other: StoreReport) {
+        self.files += other.files;
+        self.duplicates += other.duplicates;
+        self.reclaimed += other.reclaimed;
+    }
+}
+
+// Downloaded files by the SHA-256 of their content. Files in the course trees are replaced by links to the stored file with the same content,
+// so that identical files in several folders and courses only take up space once. In copy mode, files are copied from the store instead,
+// which file systems with reflinks (btrfs, XFS) can do without duplicating the data.
+#[derive(Debug, Clone)]
+pub struct ContentStore {
+    dir: PathBuf,
+    pub mode: LinkMode,
+}
+
+impl ContentStore {
+    pub fn new(dir: &Path, mode: LinkMode) -> ContentStore {
+        ContentStore {
+            dir: dir.to_path_buf(),
+            mode,
+        }
+    }
+
+    // The store is enabled by BBCM_STORE_MODE. Links need a work dir that was chosen with BBCM_WORK_DIR,
+    // as the default one is in the temp dir, and on the same file system as the output dir.
+    pub fn from_env(work_dir: &Path, out_dir: &Path) -> Result<Option<ContentStore>, String> {
+        let mode = match std::env::var("BBCM_STORE_MODE") {
+            Ok(val) if !val.is_empty() => val.parse::<LinkMode>()?,
+            _ => return Ok(None),
+        };
+        let store = ContentStore::new(&work_dir.join("store"), mode);
+        if mode != LinkMode::Copy {
+            if std::env::var("BBCM_WORK_DIR").map(|val| val.is_empty()).unwrap_or(true) {
+                return Err(format!("BBCM_STORE_MODE={} needs BBCM_WORK_DIR to be set, so that the content store isn't kept in the temp dir", mode.as_str()));
+            }
+            if !ContentStore::same_device(work_dir, out_dir) {
+                return Err(format!("BBCM_STORE_MODE={} needs the work dir {:?} on the same file system as the output dir {:?}", mode.as_str(), work_dir, out_dir));
+            }
+        }
+        Ok(Some(store))
+    }
+
+    #[cfg(unix)]
+    fn same_device(path1: &Path, path2: &Path) -> bool {
+        use std::os::unix::fs::MetadataExt;
+        match (std::fs::metadata(path1), std::fs::metadata(path2)) {
+            (Ok(metadata1), Ok(metadata2)) => metadata1.dev() == metadata2.dev(),
+            _ => false,
+        }
+    }
+
+    // Other platforms report failed links when they are made
+    #[cfg(not(unix))]
+    fn same_device(_path1: &Path, _path2: &Path) -> bool {
+        true
+    }
+
+    fn object_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(&hash[..2]).join(hash)
+    }
+
+    // Adds a file, or all files below a folder
+    pub fn add(&self, path: &Path) -> Result<StoreReport, Box<dyn std::error::Error>> {
+        let mut report = StoreReport::default();
+        let mut files = Vec::new();
+        ContentStore::files_below(path, &mut files);
+        for file in files {
+            report.files += 1;
+            if let Some(size) = self.add_file(&file)? {
+                report.duplicates += 1;
+                if self.mode != LinkMode::Copy {
+                    report.reclaimed += size;
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    // Returns the size of the file if it was replaced by the stored file with the same content
+    fn add_file(&self, path: &Path) -> Result<Option<u64>, Box<dyn std::error::Error>> {
+        let store_dir = self.dir.canonicalize().unwrap_or_else(|_| self.dir.clone());
+        if std::fs::read_link(path).map(|target| target.starts_with(&store_dir)).unwrap_or(false) {
+            return Ok(None); // Already linked
+        }
+        let hash = file_sha256(path)?;
+        let object_path = self.object_path(&hash);
+        // A stored file that was edited in place through one of its links no longer has the content of its hash
+        if object_path.exists() && !ContentStore::same_file(path, &object_path) && file_sha256(&object_path)? != hash {
+            match self.mode {
+                LinkMode::Symlink => {
+                    eprintln!("Note: {:?} in the content store was changed through a link, so {:?} is not linked to it", object_path, path);
+                    return Ok(None);
+                },
+                // The other links keep the changed content, and the file is stored again
+                LinkMode::Hardlink | LinkMode::Copy => std::fs::remove_file(&object_path)?,
+            }
+        }
+        if !object_path.exists() {
+            std::fs::create_dir_all(object_path.parent().unwrap())?;
+            match self.mode {
+                LinkMode::Hardlink => std::fs::hard_link(path, &object_path).map_err(|err| {
+                    format!("Could not link {:?} into the content store at {:?}: {} (hardlinks need the work dir on the same file system, see BBCM_STORE_MODE)", path, self.dir, err)
+                })?,
+                // The file is only moved into the store if it stays on the same file system, and never into the temp dir,
+                // as the store then holds the only copy of it
+                LinkMode::Symlink => {
+                    let store_dir = self.dir.canonicalize()?;
+                    let temp_dir = std::env::temp_dir().canonicalize().unwrap_or_else(|_| std::env::temp_dir());
+                    if store_dir.starts_with(&temp_dir) || !ContentStore::same_device(path, &store_dir) {
+                        return Err(format!("Not moving {:?} into the content store at {:?}, which is in the temp dir or on another file system", path, self.dir).into());
+                    }
+                    std::fs::rename(path, &object_path)?;
+                    if let Err(err) = self.link(&object_path, path) {
+                        let _ = std::fs::rename(&object_path, path);
+                        return Err(err);
+                    }
+                },
+                LinkMode::Copy => {
+                    std::fs::copy(path, &object_path)?;
+                },
+            }
+            return Ok(None);
+        }
+        if ContentStore::same_file(path, &object_path) {
+            return Ok(None);
+        }
+        let size = std::fs::metadata(path)?.len();
+        self.link(&object_path, path)?;
+        Ok(Some(size))
+    }
+
+    // Replaces path through a rename, so that a failed link never leaves the file missing
+    fn link(&self, object_path: &Path, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_path = path.with_file_name(format!("{}.bbcm-link", path.file_name().unwrap().to_string_lossy()));
+        let _ = std::fs::remove_file(&tmp_path);
+        match self.mode {
+            LinkMode::Hardlink => std::fs::hard_link(object_path, &tmp_path)?,
+            LinkMode::Symlink => {
+                let object_path = object_path.canonicalize()?;
+                #[cfg(unix)]
+                std::os::unix::fs::symlink(object_path, &tmp_path)?;
+                #[cfg(windows)]
+                std::os::windows::fs::symlink_file(object_path, &tmp_path)?;
+            },
+            LinkMode::Copy => {
+                std::fs::copy(object_path, &tmp_path)?; // Reflinked where the file system supports it
+            },
+        }
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn file_id(path: &Path) -> Option<(u64, u64)> {
+        use std::os::unix::fs::MetadataExt;
+        std::fs::metadata(path).ok().map(|metadata| (metadata.dev(), metadata.ino()))
+    }
+
+    #[cfg(not(unix))]
+    fn file_id(_path: &Path) -> Option<(u64, u64)> {
+        None
+    }
+
+    // Symlinks are followed, so a file symlinked to the store is the same file as the stored one
+    fn same_file(path1: &Path, path2: &Path) -> bool {
+        match (ContentStore::file_id(path1), ContentStore::file_id(path2)) {
+            (Some(id1), Some(id2)) => id1 == id2,
+            _ => false,
+        }
+    }
+
+    // What adding the files would reclaim, without changing anything
+    pub fn analyse(&self, paths: &[PathBuf]) -> Result<StoreReport, Box<dyn std::error::Error>> {
+        let mut report = StoreReport::default();
+        let mut seen: HashMap<String, HashSet<Option<(u64, u64)>>> = HashMap::new();
+        let mut files = Vec::new();
+        for path in paths {
+            ContentStore::files_below(path, &mut files);
+        }
+        for file in files {
+            let hash = file_sha256(&file)?;
+            let object_path = self.object_path(&hash);
+            let ids = seen.entry(hash).or_insert_with(|| {
+                std::iter::once(ContentStore::file_id(&object_path)).filter(|_| object_path.exists()).collect()
+            });
+            let id = ContentStore::file_id(&file);
+            report.files += 1;
+            if id.is_some() && ids.contains(&id) {
+                continue; // Already shared
+            }
+            if !ids.is_empty() {
+                report.duplicates += 1;
+                if self.mode != LinkMode::Copy {
+                    report.reclaimed += std::fs::metadata(&file)?.len();
+                }
+            }
+            ids.insert(id);
+        }
+        Ok(report)
+    }
+
+    fn files_below(path: &Path, files: &mut Vec<PathBuf>) {
+        if path.is_dir() {
+            for entry in std::fs::read_dir(path).into_iter().flatten().filter_map(|entry| entry.ok()) {
+                ContentStore::files_below(&entry.path(), files);
+            }
+        } else if path.is_file() {
+            files.push(path.to_path_buf());
+        }
+    }
+
+    // Stored files that no course tree links to anymore. Only hardlinks can be counted, so the other modes keep everything.
+    pub fn prune(&self) -> Result<(usize, u64), Box<dyn std::error::Error>> {
+        let mut removed = (0, 0);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            if self.mode == LinkMode::Hardlink && self.dir.exists() {
+                let mut objects = Vec::new();
+                ContentStore::files_below(&self.dir, &mut objects);
+                for object_path in objects {
+                    let metadata = std::fs::metadata(&object_path)?;
+                    if metadata.nlink() == 1 {
+                        std::fs::remove_file(&object_path)?;
+                        removed.0 += 1;
+                        removed.1 += metadata.len();
+                    }
+                }
+            }
+        }
+        Ok(removed)
+    }
+}
@@ -0,0 +1,160 @@
+use std::path::Path;
+use std::io::{Read, Write};
+use sha2::{Digest, Sha256};
+use super::CourseAnnouncement;
+use crate::bb_course::bb_announcement::bb_user::BBUserCache;
+use crate::utils::markup_utils::{escape_xml, sanitize_html};
+use crate::utils::time_utils::{partial_cmp_dt, utc_now, utc_rfc3339, utc_rfc2822};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FeedFormat {
+    Atom,
+    Rss,
+}
+
+impl FeedFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "atom",
+            FeedFormat::Rss => "rss",
+        }
+    }
+}
+
+impl std::str::FromStr for FeedFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<FeedFormat, String> {
+        match s {
+            "atom" => Ok(FeedFormat::Atom),
+            "rss" => Ok(FeedFormat::Rss),
+            _ => Err(format!("Unknown feed format \"{}\" (expected atom or rss)", s)),
+        }
+    }
+}
+
+pub struct AnnouncementFeed<'a> {
+    pub id: String,
+    pub title: String,
+    pub link: String,
+    pub entries: Vec<CourseAnnouncement<'a>>,
+}
+
+impl<'a> AnnouncementFeed<'a> {
+    const FINGERPRINT_PREFIX: &'static str = "<!-- bbcm-fingerprint: ";
+
+    pub fn new(id: &str, title: &str, link: &str, mut entries: Vec<CourseAnnouncement<'a>>) -> AnnouncementFeed<'a> {
+        // Newest first, as feed readers expect
+        entries.sort_by(|e1, e2| {
+            partial_cmp_dt(&e2.announcement.created, &e1.announcement.created).unwrap_or(std::cmp::Ordering::Equal)
+        });
+        AnnouncementFeed {
+            id: id.to_string(),
+            title: title.to_string(),
+            link: link.to_string(),
+            entries,
+        }
+    }
+
+    // Changes whenever an announcement is added, removed or edited, or a creator name is resolved.
+    // SHA-256, since the fingerprint is compared with files written by earlier builds.
+    fn fingerprint(&self, users: &BBUserCache, format: FeedFormat) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{}\0{}\0{}\n", format.extension(), self.title, self.link).as_bytes());
+        for entry in &self.entries {
+            hasher.update(format!("{}\0{}\0{}\0{}\n",
+                entry.course_alias,
+                entry.announcement.id,
+                entry.announcement.modified,
+                users.display_name(&entry.announcement.creator),
+            ).as_bytes());
+        }
+        hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn updated(&self) -> String {
+        self.entries.iter()
+            .map(|entry| &entry.announcement.modified)
+            .max_by(|m1, m2| partial_cmp_dt(m1, m2).unwrap_or(std::cmp::Ordering::Equal))
+            .and_then(|modified| utc_rfc3339(modified))
+            .unwrap_or_else(|| utc_rfc3339(&utc_now()).unwrap())
+    }
+
+    // Returns false if the existing file at out_path was generated from the same announcements.
+    pub fn write(&self, users: &BBUserCache, format: FeedFormat, out_path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+        let fingerprint_line = format!("{}{} -->", AnnouncementFeed::FINGERPRINT_PREFIX, self.fingerprint(users, format));
+        if out_path.exists() {
+            let mut existing = String::new();
+            std::fs::File::open(out_path)?.read_to_string(&mut existing)?;
+            if existing.lines().any(|line| line == fingerprint_line) {
+                return Ok(false);
+            }
+        }
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        xml.push_str(&fingerprint_line);
+        xml.push('\n');
+        match format {
+            FeedFormat::Atom => self.push_atom(users, &mut xml),
+            FeedFormat::Rss => self.push_rss(users, &mut xml),
+        }
+
+        let mut feed_file = std::fs::File::create(out_path)?;
+        feed_file.write_all(xml.as_bytes())?;
+        Ok(true)
+    }
+
+    fn entry_id(entry: &CourseAnnouncement) -> String {
+        format!("urn:bbcm:announcement:{}:{}", entry.course_id, entry.announcement.id)
+    }
+
+    fn push_atom(&self, users: &BBUserCache, xml: &mut String) {
+        xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+        xml.push_str(&format!("  <id>{}</id>\n", escape_xml(&self.id)));
+        xml.push_str(&format!("  <title>{}</title>\n", escape_xml(&self.title)));
+        xml.push_str(&format!("  <link href=\"{}\"/>\n", escape_xml(&self.link)));
+        xml.push_str(&format!("  <updated>{}</updated>\n", self.updated()));
+        xml.push_str("  <generator>bbcm</generator>\n");
+        for entry in &self.entries {
+            let announcement = entry.announcement;
+            let updated = utc_rfc3339(&announcement.modified).unwrap_or_else(|| self.updated());
+            xml.push_str("  <entry>\n");
+            xml.push_str(&format!("    <id>{}</id>\n", escape_xml(&AnnouncementFeed::entry_id(entry))));
+            xml.push_str(&format!("    <title>[{}] {}</title>\n", escape_xml(entry.course_alias), escape_xml(&announcement.title)));
+            xml.push_str(&format!("    <author><name>{}</name></author>\n", escape_xml(&users.display_name(&announcement.creator))));
+            xml.push_str(&format!("    <category term=\"{}\"/>\n", escape_xml(entry.course_alias)));
+            if let Some(published) = utc_rfc3339(&announcement.created) {
+                xml.push_str(&format!("    <published>{}</published>\n", published));
+            }
+            xml.push_str(&format!("    <updated>{}</updated>\n", updated));
+            xml.push_str(&format!("    <content type=\"html\">{}</content>\n", escape_xml(&sanitize_html(&announcement.body))));
+            xml.push_str("  </entry>\n");
+        }
+        xml.push_str("</feed>\n");
+    }
+
+    fn push_rss(&self, users: &BBUserCache, xml: &mut String) {
+        xml.push_str("<rss version=\"2.0\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n");
+        xml.push_str("  <channel>\n");
+        xml.push_str(&format!("    <title>{}</title>\n", escape_xml(&self.title)));
+        xml.push_str(&format!("    <link>{}</link>\n", escape_xml(&self.link)));
+        xml.push_str(&format!("    <description>{}</description>\n", escape_xml(&self.title)));
+        xml.push_str(&format!("    <lastBuildDate>{}</lastBuildDate>\n", utc_rfc2822(&self.updated()).unwrap()));
+        xml.push_str("    <generator>bbcm</generator>\n");
+        for entry in &self.entries {
+            let announcement = entry.announcement;
+            xml.push_str("    <item>\n");
+            xml.push_str(&format!("      <guid isPermaLink=\"false\">{}</guid>\n", escape_xml(&AnnouncementFeed::entry_id(entry))));
+            xml.push_str(&format!("      <title>[{}] {}</title>\n", escape_xml(entry.course_alias), escape_xml(&announcement.title)));
+            xml.push_str(&format!("      <dc:creator>{}</dc:creator>\n", escape_xml(&users.display_name(&announcement.creator))));
+            xml.push_str(&format!("      <category>{}</category>\n", escape_xml(entry.course_alias)));
+            if let Some(pub_date) = utc_rfc2822(&announcement.created) {
+                xml.push_str(&format!("      <pubDate>{}</pubDate>\n", pub_date));
+            }
+            xml.push_str(&format!("      <description>{}</description>\n", escape_xml(&sanitize_html(&announcement.body))));
+            xml.push_str("    </item>\n");
+        }
+        xml.push_str("  </channel>\n");
+        xml.push_str("</rss>\n");
+    }
+}
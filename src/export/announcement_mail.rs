@@ -0,0 +1,191 @@
+use std::path::{Path, PathBuf};
+use std::io::{Read, Write};
+use std::collections::HashMap;
+use super::CourseAnnouncement;
+use crate::bb_session::BBSession;
+use crate::bb_course::bb_announcement::bb_user::BBUserCache;
+use crate::utils::markup_utils::sanitize_html;
+use crate::utils::mail_utils::{encode_header_value, quoted_display_name, base64_body, message_id_part};
+use crate::utils::time_utils::{utc_now, utc_rfc2822, utc_timestamp, utc_asctime};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MailFormat {
+    Maildir,
+    Mbox,
+}
+
+impl MailFormat {
+    pub fn default_path(&self, out_dir: &Path) -> PathBuf {
+        match self {
+            MailFormat::Maildir => out_dir.join("mail"),
+            MailFormat::Mbox => out_dir.join("announcements.mbox"),
+        }
+    }
+}
+
+impl std::str::FromStr for MailFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<MailFormat, String> {
+        match s {
+            "maildir" => Ok(MailFormat::Maildir),
+            "mbox" => Ok(MailFormat::Mbox),
+            _ => Err(format!("Unknown mail format \"{}\" (expected maildir or mbox)", s)),
+        }
+    }
+}
+
+// A Maildir or mbox that announcements are exported to.
+// The "modified" value of every exported announcement is kept in a state file, so that edits are exported as updates.
+pub struct AnnouncementMailbox {
+    path: PathBuf,
+    format: MailFormat,
+    exported: HashMap<String, String>,
+}
+
+impl AnnouncementMailbox {
+    const VIEW_WIDTH: usize = 78;
+
+    fn state_path(path: &Path, format: MailFormat) -> PathBuf {
+        match format {
+            MailFormat::Maildir => path.join(".bbcm_exported.json"),
+            MailFormat::Mbox => path.with_extension("bbcm.json"),
+        }
+    }
+
+    pub fn open(path: &Path, format: MailFormat) -> Result<AnnouncementMailbox, Box<dyn std::error::Error>> {
+        match format {
+            MailFormat::Maildir => {
+                for sub_dir in &["tmp", "new", "cur"] {
+                    std::fs::create_dir_all(path.join(sub_dir))?;
+                }
+            },
+            MailFormat::Mbox => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+            },
+        }
+        let mut exported = HashMap::new();
+        let state_path = AnnouncementMailbox::state_path(path, format);
+        if state_path.exists() {
+            let mut json_string = String::new();
+            std::fs::File::open(&state_path)?.read_to_string(&mut json_string)?;
+            for (id, modified) in json::parse(&json_string)?.entries() {
+                exported.insert(id.to_string(), modified.to_string());
+            }
+        }
+        Ok(AnnouncementMailbox {
+            path: path.to_path_buf(),
+            format,
+            exported,
+        })
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut state = json::JsonValue::new_object();
+        for (id, modified) in &self.exported {
+            state[id.as_str()] = modified.clone().into();
+        }
+        let mut state_file = std::fs::File::create(AnnouncementMailbox::state_path(&self.path, self.format))?;
+        state_file.write_all(state.pretty(4).as_bytes())?;
+        Ok(())
+    }
+
+    // Returns false if the announcement has already been exported in its current version.
+    pub fn export(&mut self, session: &BBSession, users: &BBUserCache, entry: &CourseAnnouncement) -> Result<bool, Box<dyn std::error::Error>> {
+        let announcement = entry.announcement;
+        let is_update = match self.exported.get(&announcement.id) {
+            Some(modified) if modified == &announcement.modified => return Ok(false),
+            Some(_) => true,
+            None => false,
+        };
+        let message = AnnouncementMailbox::message(session, users, entry, is_update);
+        match self.format {
+            MailFormat::Maildir => self.deliver_maildir(entry, &message)?,
+            MailFormat::Mbox => self.append_mbox(entry, &message)?,
+        }
+        self.exported.insert(announcement.id.clone(), announcement.modified.clone());
+        Ok(true)
+    }
+
+    fn deliver_maildir(&self, entry: &CourseAnnouncement, message: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let announcement = entry.announcement;
+        let filename = format!("{}.{}_{}_{}.bbcm",
+            utc_timestamp(&announcement.created).unwrap_or(0),
+            message_id_part(entry.course_id),
+            message_id_part(&announcement.id),
+            utc_timestamp(&announcement.modified).unwrap_or(0),
+        );
+        // Maildir delivery: write to tmp, then move into new
+        let tmp_path = self.path.join("tmp").join(&filename);
+        std::fs::File::create(&tmp_path)?.write_all(message.as_bytes())?;
+        std::fs::rename(&tmp_path, self.path.join("new").join(&filename))?;
+        Ok(())
+    }
+
+    fn append_mbox(&self, entry: &CourseAnnouncement, message: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut mbox = std::fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        let date = utc_asctime(&entry.announcement.modified).or_else(|| utc_asctime(&utc_now())).unwrap();
+        let mut quoted = format!("From bbcm {}\n", date);
+        // mboxrd quoting of lines that could be mistaken for separators
+        for line in message.lines() {
+            if line.trim_start_matches('>').starts_with("From ") {
+                quoted.push('>');
+            }
+            quoted.push_str(line);
+            quoted.push('\n');
+        }
+        quoted.push('\n');
+        mbox.write_all(quoted.as_bytes())?;
+        Ok(())
+    }
+
+    fn message_id(session: &BBSession, entry: &CourseAnnouncement, version: Option<i64>) -> String {
+        let local_part = match version {
+            Some(version) => format!("announcement.{}.{}", message_id_part(&entry.announcement.id), version),
+            None => format!("announcement.{}", message_id_part(&entry.announcement.id)),
+        };
+        format!("<{}@{}.{}>", local_part, message_id_part(entry.course_id), session.domain)
+    }
+
+    fn message(session: &BBSession, users: &BBUserCache, entry: &CourseAnnouncement, is_update: bool) -> String {
+        let announcement = entry.announcement;
+        let creator_name = users.display_name(&announcement.creator);
+        let original_id = AnnouncementMailbox::message_id(session, entry, None);
+        let boundary = format!("bbcm-{}", message_id_part(&announcement.id));
+        let subject = if is_update {
+            format!("[{}] {} (updated)", entry.course_alias, announcement.title)
+        } else {
+            format!("[{}] {}", entry.course_alias, announcement.title)
+        };
+        let date = if is_update { &announcement.modified } else { &announcement.created };
+
+        let mut headers = vec![
+            format!("From: {} <{}@{}>", quoted_display_name(&creator_name), message_id_part(&announcement.creator), session.domain),
+            format!("To: {} <{}@{}>", quoted_display_name(entry.course_alias), message_id_part(entry.course_id), session.domain),
+            format!("Subject: {}", encode_header_value(&subject)),
+            format!("Date: {}", utc_rfc2822(date).or_else(|| utc_rfc2822(&utc_now())).unwrap()),
+            format!("List-Id: {} <{}.{}>", quoted_display_name(entry.course_alias), message_id_part(entry.course_id), session.domain),
+        ];
+        if is_update {
+            headers.push(format!("Message-ID: {}", AnnouncementMailbox::message_id(session, entry, utc_timestamp(&announcement.modified))));
+            headers.push(format!("In-Reply-To: {}", original_id));
+            headers.push(format!("References: {}", original_id));
+        } else {
+            headers.push(format!("Message-ID: {}", original_id));
+        }
+        headers.push(String::from("MIME-Version: 1.0"));
+        headers.push(format!("Content-Type: multipart/alternative; boundary=\"{}\"", boundary));
+
+        let text = html2text::from_read(announcement.body.as_bytes(), AnnouncementMailbox::VIEW_WIDTH);
+        let html = format!("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"></head><body>{}</body></html>", sanitize_html(&announcement.body));
+
+        format!("{}\n\n--{b}\nContent-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: base64\n\n{}\n--{b}\nContent-Type: text/html; charset=utf-8\nContent-Transfer-Encoding: base64\n\n{}\n--{b}--\n",
+            headers.join("\n"),
+            base64_body(text.as_bytes()),
+            base64_body(html.as_bytes()),
+            b = boundary,
+        )
+    }
+}
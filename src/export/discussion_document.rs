@@ -0,0 +1,100 @@
+use std::path::Path;
+use std::collections::HashMap;
+use crate::bb_course::bb_discussion::bb_message::BBMessage;
+use crate::bb_course::bb_announcement::bb_user::BBUserCache;
+use crate::utils::markup_utils::{escape_xml, sanitize_html};
+use crate::utils::time_utils::local_rfc2822;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocumentFormat {
+    Markdown,
+    Html,
+}
+
+impl DocumentFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DocumentFormat::Markdown => "md",
+            DocumentFormat::Html => "html",
+        }
+    }
+}
+
+impl std::str::FromStr for DocumentFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DocumentFormat, String> {
+        match s {
+            "markdown" | "md" => Ok(DocumentFormat::Markdown),
+            "html" => Ok(DocumentFormat::Html),
+            _ => Err(format!("Unknown document format \"{}\" (expected markdown or html)", s)),
+        }
+    }
+}
+
+// A discussion thread rendered as one document, with replies nested under the message they answer
+pub struct ThreadDocument<'a> {
+    pub discussion_title: &'a str,
+    pub thread: &'a BBMessage,
+    pub replies: Vec<&'a BBMessage>,
+}
+
+impl<'a> ThreadDocument<'a> {
+    const VIEW_WIDTH: usize = 100;
+
+    fn children(&self) -> HashMap<&str, Vec<&'a BBMessage>> {
+        let mut children: HashMap<&str, Vec<&BBMessage>> = HashMap::new();
+        for reply in &self.replies {
+            children.entry(reply.parent_id.as_str()).or_default().push(reply);
+        }
+        children
+    }
+
+    pub fn write(&self, users: &BBUserCache, format: DocumentFormat, out_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let children = self.children();
+        let mut document = String::new();
+        match format {
+            DocumentFormat::Markdown => {
+                document.push_str(&format!("# {}\n\n_{}_\n\n", self.thread.title, self.discussion_title));
+                ThreadDocument::push_markdown(users, &children, self.thread, 0, &mut document);
+            },
+            DocumentFormat::Html => {
+                document.push_str(&format!("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{0}</title></head><body>\n<h1>{0}</h1>\n<p><em>{1}</em></p>\n",
+                    escape_xml(&self.thread.title),
+                    escape_xml(self.discussion_title)));
+                ThreadDocument::push_html(users, &children, self.thread, &mut document);
+                document.push_str("</body></html>\n");
+            },
+        }
+        std::fs::write(out_path, document)?;
+        Ok(())
+    }
+
+    fn push_markdown(users: &BBUserCache, children: &HashMap<&str, Vec<&BBMessage>>, message: &BBMessage, depth: usize, document: &mut String) {
+        let quote = "> ".repeat(depth);
+        document.push_str(&format!("{}**{}** ({})\n{}\n", quote, users.display_name(&message.creator), local_rfc2822(&message.created), quote.trim_end()));
+        for line in html2text::from_read(message.body.as_bytes(), ThreadDocument::VIEW_WIDTH).lines() {
+            document.push_str(&format!("{}{}\n", quote, line));
+        }
+        document.push('\n');
+        for reply in children.get(message.id.as_str()).into_iter().flatten() {
+            ThreadDocument::push_markdown(users, children, reply, depth + 1, document);
+        }
+    }
+
+    fn push_html(users: &BBUserCache, children: &HashMap<&str, Vec<&BBMessage>>, message: &BBMessage, document: &mut String) {
+        document.push_str(&format!("<article>\n<p><strong>{}</strong> <time datetime=\"{}\">{}</time></p>\n{}\n",
+            escape_xml(&users.display_name(&message.creator)),
+            escape_xml(&message.created),
+            escape_xml(&local_rfc2822(&message.created)),
+            sanitize_html(&message.body)));
+        if let Some(replies) = children.get(message.id.as_str()) {
+            document.push_str("<blockquote>\n");
+            for reply in replies {
+                ThreadDocument::push_html(users, children, reply, document);
+            }
+            document.push_str("</blockquote>\n");
+        }
+        document.push_str("</article>\n");
+    }
+}
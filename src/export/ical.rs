@@ -0,0 +1,126 @@
+use std::path::Path;
+use std::io::Write;
+use chrono::Duration;
+use crate::bb_session::BBSession;
+use crate::bb_course::BBCourse;
+use crate::bb_course::bb_gradebook::BBGradebookColumn;
+use crate::bb_course::bb_calendar::BBCalendarItem;
+use crate::utils::time_utils::{utc_now, utc_ical};
+use crate::utils::content_line_utils::{escape_text, push_content_line};
+
+pub struct ICalEvent {
+    pub uid: String,
+    pub summary: String,
+    pub description: String,
+    pub location: String,
+    pub start: String,
+    pub end: String,
+    pub modified: String,
+    pub url: String,
+}
+
+impl ICalEvent {
+    pub fn from_gradebook_column(session: &BBSession, course: &BBCourse, gbc: &BBGradebookColumn) -> ICalEvent {
+        ICalEvent {
+            uid: format!("gradebook-column-{}-{}@{}", course.id, gbc.id, session.domain),
+            summary: format!("[{}] {} (due)", course.alias, gbc.name),
+            description: String::new(),
+            location: String::new(),
+            start: gbc.due.clone(),
+            end: gbc.due.clone(),
+            modified: String::new(),
+            url: course.web_url(session),
+        }
+    }
+
+    pub fn from_calendar_item(session: &BBSession, course: &BBCourse, item: &BBCalendarItem) -> ICalEvent {
+        ICalEvent {
+            uid: format!("calendar-item-{}-{}@{}", course.id, item.id, session.domain),
+            summary: format!("[{}] {}", course.alias, item.title),
+            description: html2text::from_read(item.description.as_bytes(), 100),
+            location: item.location.clone(),
+            start: item.start.clone(),
+            end: item.end.clone(),
+            modified: item.modified.clone(),
+            url: course.web_url(session),
+        }
+    }
+}
+
+// RFC 5545 calendar with one VEVENT per deadline or calendar item
+pub struct ICalendar {
+    pub name: String,
+    pub alarms: Vec<Duration>,
+    pub events: Vec<ICalEvent>,
+}
+
+impl ICalendar {
+    pub fn new(name: &str, alarms: &[Duration]) -> ICalendar {
+        ICalendar {
+            name: name.to_string(),
+            alarms: alarms.to_vec(),
+            events: Vec::new(),
+        }
+    }
+
+    fn trigger(alarm: &Duration) -> String {
+        let mut seconds = alarm.num_seconds();
+        let days = seconds / (24*60*60);
+        seconds %= 24*60*60;
+        let hours = seconds / (60*60);
+        seconds %= 60*60;
+        let minutes = seconds / 60;
+        seconds %= 60;
+        format!("-P{}DT{}H{}M{}S", days, hours, minutes, seconds)
+    }
+
+    pub fn write(&self, out_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let dtstamp = utc_ical(&utc_now()).unwrap();
+        let mut ics = String::new();
+        push_content_line(&mut ics, "BEGIN:VCALENDAR");
+        push_content_line(&mut ics, "VERSION:2.0");
+        push_content_line(&mut ics, "PRODID:-//bbcm//Blackboard Course Manager//EN");
+        push_content_line(&mut ics, "CALSCALE:GREGORIAN");
+        push_content_line(&mut ics, &format!("X-WR-CALNAME:{}", escape_text(&self.name)));
+        for event in &self.events {
+            let start = match utc_ical(&event.start) {
+                Some(start) => start,
+                None => continue,
+            };
+            let end = utc_ical(&event.end).unwrap_or_else(|| start.clone());
+            push_content_line(&mut ics, "BEGIN:VEVENT");
+            push_content_line(&mut ics, &format!("UID:{}", event.uid));
+            push_content_line(&mut ics, &format!("DTSTAMP:{}", dtstamp));
+            if let Some(modified) = utc_ical(&event.modified) {
+                push_content_line(&mut ics, &format!("LAST-MODIFIED:{}", modified));
+            }
+            push_content_line(&mut ics, &format!("DTSTART:{}", start));
+            push_content_line(&mut ics, &format!("DTEND:{}", end));
+            push_content_line(&mut ics, &format!("SUMMARY:{}", escape_text(&event.summary)));
+            if !event.description.is_empty() {
+                push_content_line(&mut ics, &format!("DESCRIPTION:{}", escape_text(&event.description)));
+            }
+            if !event.location.is_empty() {
+                push_content_line(&mut ics, &format!("LOCATION:{}", escape_text(&event.location)));
+            }
+            if !event.url.is_empty() {
+                push_content_line(&mut ics, &format!("URL:{}", event.url));
+            }
+            for alarm in &self.alarms {
+                push_content_line(&mut ics, "BEGIN:VALARM");
+                push_content_line(&mut ics, "ACTION:DISPLAY");
+                push_content_line(&mut ics, &format!("DESCRIPTION:{}", escape_text(&event.summary)));
+                push_content_line(&mut ics, &format!("TRIGGER:{}", ICalendar::trigger(alarm)));
+                push_content_line(&mut ics, "END:VALARM");
+            }
+            push_content_line(&mut ics, "END:VEVENT");
+        }
+        push_content_line(&mut ics, "END:VCALENDAR");
+
+        // Write to a temporary file first, so that subscribed calendar apps never read a half-written file
+        let tmp_path = out_path.with_extension("ics.tmp");
+        std::fs::File::create(&tmp_path)?.write_all(ics.as_bytes())?;
+        std::fs::rename(&tmp_path, out_path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,14 @@
+pub mod announcement_feed;
+pub mod announcement_mail;
+pub mod discussion_document;
+pub mod ical;
+pub mod roster;
+
+use crate::bb_course::bb_announcement::BBAnnouncement;
+
+// An announcement together with the course it was posted in
+pub struct CourseAnnouncement<'a> {
+    pub course_alias: &'a str,
+    pub course_id: &'a str,
+    pub announcement: &'a BBAnnouncement,
+}
@@ -0,0 +1,48 @@
+use std::path::Path;
+use crate::bb_course::bb_membership::BBMembership;
+use crate::utils::content_line_utils::{escape_text, push_content_line};
+
+fn csv_field(s: &str) -> String {
+    if s.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+pub fn write_csv(memberships: &[BBMembership], out_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut csv = String::from("role,name,username,email\r\n");
+    for membership in memberships {
+        let fields = [
+            &membership.role,
+            &membership.user.name,
+            &membership.user.user_name,
+            &membership.user.email,
+        ];
+        csv.push_str(&fields.iter().map(|field| csv_field(field)).collect::<Vec<String>>().join(","));
+        csv.push_str("\r\n");
+    }
+    std::fs::write(out_path, csv)?;
+    Ok(())
+}
+
+// vCard 3.0, with the course and role in ORG and ROLE
+pub fn write_vcard(memberships: &[BBMembership], course_name: &str, out_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut vcard = String::new();
+    for membership in memberships {
+        let user = &membership.user;
+        push_content_line(&mut vcard, "BEGIN:VCARD");
+        push_content_line(&mut vcard, "VERSION:3.0");
+        push_content_line(&mut vcard, &format!("FN:{}", escape_text(&user.name)));
+        push_content_line(&mut vcard, &format!("N:{};{};;;", escape_text(&user.family_name), escape_text(&user.given_name)));
+        if !user.email.is_empty() {
+            push_content_line(&mut vcard, &format!("EMAIL;TYPE=INTERNET:{}", escape_text(&user.email)));
+        }
+        push_content_line(&mut vcard, &format!("ORG:{}", escape_text(course_name)));
+        push_content_line(&mut vcard, &format!("ROLE:{}", escape_text(&membership.role)));
+        push_content_line(&mut vcard, &format!("UID:{}", escape_text(&user.id)));
+        push_content_line(&mut vcard, "END:VCARD");
+    }
+    std::fs::write(out_path, vcard)?;
+    Ok(())
+}
@@ -0,0 +1,168 @@
+use std::path::{Path, PathBuf};
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use crate::watch::{WatchEvent, WatchEventKind};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HookTrigger {
+    File,
+    Course,
+    Announcement,
+}
+
+impl HookTrigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookTrigger::File => "file",
+            HookTrigger::Course => "course",
+            HookTrigger::Announcement => "announcement",
+        }
+    }
+
+    fn matches(&self, kind: WatchEventKind) -> bool {
+        match self {
+            HookTrigger::File => kind == WatchEventKind::NewFile || kind == WatchEventKind::UpdatedFile,
+            HookTrigger::Course => kind == WatchEventKind::CourseSynced,
+            HookTrigger::Announcement => kind == WatchEventKind::NewAnnouncement,
+        }
+    }
+}
+
+impl std::str::FromStr for HookTrigger {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<HookTrigger, String> {
+        match s {
+            "file" => Ok(HookTrigger::File),
+            "course" => Ok(HookTrigger::Course),
+            "announcement" => Ok(HookTrigger::Announcement),
+            _ => Err(format!("Unknown hook trigger \"{}\" (expected file, course or announcement)", s)),
+        }
+    }
+}
+
+// A shell command run for each matching event. Hooks without a course alias run for all courses.
+pub struct Hook {
+    pub command: String,
+    pub trigger: HookTrigger,
+    pub course_alias: Option<String>,
+    pub timeout: Duration,
+}
+
+impl Hook {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+    pub fn view(&self, index: usize) {
+        println!("{}: on {} for {} (timeout {}s): {}",
+            index,
+            self.trigger.as_str(),
+            self.course_alias.as_deref().unwrap_or("all courses"),
+            self.timeout.as_secs(),
+            self.command,
+        );
+    }
+}
+
+// The hooks configured in hooks.json in the work dir
+pub struct Hooks {
+    path: PathBuf,
+    pub hooks: Vec<Hook>,
+}
+
+impl Hooks {
+    pub fn load(path: &Path) -> Result<Hooks, Box<dyn std::error::Error>> {
+        let mut hooks = Vec::new();
+        if path.exists() {
+            let mut json_string = String::new();
+            std::fs::File::open(path)?.read_to_string(&mut json_string)?;
+            for member in json::parse(&json_string)?.members() {
+                hooks.push(Hook {
+                    command: member["command"].to_string(),
+                    trigger: member["trigger"].to_string().parse()?,
+                    course_alias: member["course"].as_str().map(String::from),
+                    timeout: Duration::from_secs(member["timeout"].as_u64().unwrap_or(Hook::DEFAULT_TIMEOUT.as_secs())),
+                });
+            }
+        }
+        Ok(Hooks {
+            path: path.to_path_buf(),
+            hooks,
+        })
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let json_array = json::JsonValue::Array(self.hooks.iter().map(|hook| {
+            json::object!{
+                command: hook.command.clone(),
+                trigger: hook.trigger.as_str(),
+                course: hook.course_alias.clone(),
+                timeout: hook.timeout.as_secs(),
+            }
+        }).collect());
+        let mut hooks_file = std::fs::File::create(&self.path)?;
+        hooks_file.write_all(json_array.pretty(4).as_bytes())?;
+        Ok(())
+    }
+
+    // Runs all hooks matching the event, and returns the number of hooks that failed
+    pub fn run(&self, event: &WatchEvent) -> usize {
+        let matching_hooks = self.hooks.iter().filter(|hook| {
+            hook.trigger.matches(event.kind) && hook.course_alias.as_ref().map(|alias| alias == &event.course_alias).unwrap_or(true)
+        });
+        let mut failed = 0;
+        for hook in matching_hooks {
+            if let Err(err) = run_command(&hook.command, &event.to_json_line(), &event.env_vars(), hook.timeout) {
+                eprintln!("Hook \"{}\" failed for {} in {}: {}", hook.command, event.kind.as_str(), event.course_alias, err);
+                failed += 1;
+            }
+        }
+        failed
+    }
+
+    pub fn run_all(&self, events: &[WatchEvent]) -> usize {
+        events.iter().map(|event| self.run(event)).sum()
+    }
+}
+
+// Runs a shell command with the given line on stdin. Its stdout is passed on to stderr, so that it can't be mistaken for bbcm output.
+pub fn run_command(command: &str, input: &str, env_vars: &[(&str, String)], timeout: Duration) -> Result<(), Box<dyn std::error::Error>> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    };
+    let mut child = shell
+        .envs(env_vars.iter().map(|(key, value)| (key, value)))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::from(std::io::stderr()))
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // Hooks that exit without reading stdin close the pipe early, which isn't an error
+        if let Err(err) = writeln!(stdin, "{}", input) {
+            if err.kind() != std::io::ErrorKind::BrokenPipe {
+                return Err(err.into());
+            }
+        }
+    }
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return if status.success() {
+                Ok(())
+            } else {
+                Err(format!("exited with {}", status).into())
+            };
+        }
+        if started.elapsed() > timeout {
+            child.kill()?;
+            child.wait()?;
+            return Err(format!("timed out after {}s", timeout.as_secs()).into());
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
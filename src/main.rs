@@ -1,6 +1,7 @@
 // https://rust-cli.github.io/book/index.html
 use std::path::{PathBuf, Path};
 use std::collections::HashMap;
+use std::cmp::Ordering;
 use std::io::{Read, Write};
 use structopt::StructOpt;
 
@@ -8,16 +9,50 @@ mod utils;
 mod bbcm;
 mod bb_course;
 mod bb_session;
+mod bb_term;
+mod export;
+mod watch;
+mod hooks;
+mod search;
+mod serve;
+mod content_store;
+#[cfg(feature = "notifications")]
+mod notifier;
+#[cfg(feature = "git-snapshots")]
+mod snapshot;
 
 //OBS!! Merk at std::error::Error er en trait, mens std::io::Error er en struct!!
 use bb_course::BBCourse;
+use bb_course::bb_course_info::BBCourseInfo;
+use bb_course::bb_announcement::BBAnnouncement;
+use bb_course::bb_gradebook::Urgency;
+use bb_course::bb_gradebook::bb_submission::BBSubmission;
+use bb_course::bb_membership::BBMembership;
+use bb_course::bb_announcement::bb_user::{BBUser, BBUserCache};
 use bb_session::BBSession;
+use bb_term::BBTerm;
 use utils::{
-    filename_utils::cookie_filename,
-    time_utils::utc_now,
+    filename_utils::{cookie_filename, valid_filename, valid_dir_name},
+    time_utils::{partial_cmp_dt, utc_offset_from_now},
     input_utils::stdin_trimmed_line,
 };
-use bbcm::Bbcm;
+use bbcm::{Bbcm, BbcmArgs, AnnouncementsCommand, HooksCommand};
+use hooks::{Hook, Hooks};
+use search::search_index::SearchIndex;
+use serve::CourseServer;
+use content_store::{ContentStore, StoreReport};
+use export::CourseAnnouncement;
+use export::announcement_feed::AnnouncementFeed;
+use export::announcement_mail::AnnouncementMailbox;
+use export::ical::{ICalendar, ICalEvent};
+use export::roster;
+use chrono::Duration;
+
+// The calendar items API only accepts a limited time range
+const CALENDAR_WEEKS_BEFORE: i64 = 4;
+const CALENDAR_WEEKS_AFTER: i64 = 12;
+// How far back unsubmitted deadlines are shown as overdue by default, also used by the web UI
+const OVERDUE_DAYS: i64 = 7;
 
 pub fn load_courses(json_path: &Path) -> Vec<BBCourse> {
     let mut json_string = String::new();
@@ -30,22 +65,24 @@ pub fn load_courses(json_path: &Path) -> Vec<BBCourse> {
     let courses_json = json::parse(&json_string).expect("Error parsing courses json");
     if let json::JsonValue::Array(courses) = courses_json {
         courses.into_iter().map(|course| {
-            BBCourse::new(
+            let mut bb_course = BBCourse::new(
                 &course["course_code"].to_string(),
                 &course["semester"].to_string(),
                 &course["alias"].to_string(),
                 Path::new(&course["out_dir"].to_string()),
                 &course["id"].to_string(),
                 &course["last_tree_download"].to_string(),
-            )
+            );
+            bb_course.info = BBCourseInfo::from(&course["info"]);
+            bb_course
         }).collect()
     } else {
         panic!("Unknown json format in courses file.");
     }
 }
 
-pub fn save_courses(courses: &[BBCourse], out_path: &Path) {
-    let course_objects: Vec<json::JsonValue> = courses.iter().map(|course| {
+pub fn save_courses<'a>(courses: impl Iterator<Item = &'a BBCourse>, out_path: &Path) {
+    let course_objects: Vec<json::JsonValue> = courses.map(|course| {
         json::JsonValue::from(course)
     }).collect();
     let json_array = json::JsonValue::Array(course_objects); 
@@ -57,6 +94,108 @@ pub fn save_courses(courses: &[BBCourse], out_path: &Path) {
     courses_file.write_all(json_dump.as_bytes()).expect("Error writing to courses file");
 }
 
+// Courses registered before course details were stored have no term until they are refreshed
+fn refresh_missing_info(courses: &mut HashMap<String, BBCourse>, session: &BBSession) {
+    for (alias, course) in courses.iter_mut().filter(|(_, course)| !course.info.is_refreshed()) {
+        if let Err(err) = course.refresh_info(session) {
+            eprintln!("Error refreshing details for {}: {}", alias, err);
+        }
+    }
+}
+
+// Downloads a course tree, then runs the file hooks for new and updated files and the course hooks
+fn download_tree(course: &mut BBCourse, session: &BBSession, store: Option<&ContentStore>, overwrite: bool, hooks: &Hooks) {
+    match watch::tree_events(course, session, store, overwrite) {
+        Ok((download_size, saved_size, mut events)) => {
+            println!("Downloaded a total of {:.1} MB.", download_size/1000000.0);
+            if saved_size > 0.0 {
+                println!("Skipped {:.1} MB of unchanged files.", saved_size/1000000.0);
+            }
+            events.push(watch::course_synced_event(course));
+            let failed = hooks.run_all(&events);
+            if failed > 0 {
+                eprintln!("{} hooks failed for {}.", failed, course.alias);
+            }
+        },
+        Err(err) => eprintln!("Error downloading tree for {}: {}", course.alias, err),
+    }
+}
+
+// Runs the announcement hooks for announcements that were shown for the first time
+fn run_announcement_hooks(hooks: &Hooks, course: &BBCourse, announcements: &[BBAnnouncement]) {
+    let failed: usize = announcements.iter().map(|announcement| hooks.run(&watch::announcement_event(course, announcement))).sum();
+    if failed > 0 {
+        eprintln!("{} hooks failed for {}.", failed, course.alias);
+    }
+}
+
+// Commits the course folder if git snapshots are enabled and anything changed
+#[cfg(feature = "git-snapshots")]
+fn commit_snapshot(course: &BBCourse, work_dir: &Path, users: &BBUserCache, config: Option<&snapshot::SnapshotConfig>) {
+    if let Some(config) = config {
+        match snapshot::commit_course(course, work_dir, users, config) {
+            Ok(Some(summary)) => println!("Committed snapshot: {}", summary),
+            Ok(None) => {},
+            Err(err) => eprintln!("Error committing snapshot of {}: {}", course.alias, err),
+        }
+    }
+}
+
+// Brings the search index up to date with the sync manifests of the given courses
+fn update_search_index(index: &mut SearchIndex, courses: &HashMap<String, BBCourse>, course_alias: Option<&String>, work_dir: &Path) {
+    index.retain_courses(&courses.keys().collect());
+    for (alias, course) in courses.iter().filter(|(alias, _)| course_alias.map(|a| a == *alias).unwrap_or(true)) {
+        match index.update_course(course, work_dir) {
+            Ok((0, 0)) => {},
+            Ok((indexed, removed)) => eprintln!("Indexed {} documents for {} ({} removed).", indexed, alias, removed),
+            Err(err) => eprintln!("Error indexing {}: {}", alias, err),
+        }
+    }
+}
+
+// Terms are taken from the response cache, so that listing courses doesn't need Blackboard. They are only fetched
+// with --refresh, or when the cache doesn't have the term of every course, and the cached terms are used if that fails.
+fn get_terms(session: &BBSession, courses: &HashMap<String, BBCourse>, refresh: bool) -> Vec<BBTerm> {
+    let mut offline_session = session.clone();
+    offline_session.offline = true;
+    let cached_terms = BBTerm::get_all(&offline_session).ok();
+    let is_complete = |terms: &Vec<BBTerm>| courses.values().all(|course| course.info.term_id.is_empty() || terms.iter().any(|term| term.id == course.info.term_id));
+    match cached_terms {
+        Some(terms) if !refresh && is_complete(&terms) => terms,
+        cached_terms if session.offline => cached_terms.unwrap_or_else(|| {
+            eprintln!("No cached terms, so courses aren't grouped by term.");
+            Vec::new()
+        }),
+        cached_terms => BBTerm::get_all(session).unwrap_or_else(|err| {
+            eprintln!("Error downloading terms: {}", err);
+            cached_terms.unwrap_or_default()
+        }),
+    }
+}
+
+// Resolves a --term argument, exiting if no term matches
+fn find_terms<'a>(terms: &'a [BBTerm], query: &str) -> Vec<&'a BBTerm> {
+    let matching_terms = BBTerm::find(terms, query);
+    if matching_terms.is_empty() {
+        eprintln!("No term matching \"{}\" found.", query);
+        std::process::exit(1);
+    }
+    matching_terms
+}
+
+// Registering needs a single term, so overlapping matches are listed instead of picking one
+fn find_term<'a>(terms: &'a [BBTerm], query: &str) -> &'a BBTerm {
+    let matching_terms = find_terms(terms, query);
+    if matching_terms.len() > 1 {
+        eprintln!("Several terms match \"{}\", please give one of their ids:", query);
+        for term in matching_terms {
+            eprintln!("  {}: {}", term.id, term.heading());
+        }
+        std::process::exit(1);
+    }
+    matching_terms[0]
+}
+
 fn main() {
     let domain = std::env::var("BBCM_DOMAIN").unwrap_or_else(|_| {
         println!("Please enter the blackboard domain (format: <institution>.blackboard.com):"); // This matches the NTNU courseId convention
@@ -74,22 +213,80 @@ fn main() {
     std::fs::create_dir_all(&out_dir).expect("Error creating BBCourseManager out_dir");
     std::fs::create_dir_all(&work_dir).expect("Error creating BBCourseManager work_dir");
     let cookie_jar_path = work_dir.join(cookie_filename(&domain));
-    let session = BBSession::new(&domain, &cookie_jar_path).expect("Error creating session");
+    let args = BbcmArgs::from_args();
+    let session = BBSession::new(&domain, &cookie_jar_path).with_cache(&work_dir.join("http_cache"), args.offline);
     let courses_json_path = work_dir.join("courses.json");
+    let user_cache_ttl = std::env::var("BBCM_USER_CACHE_TTL").ok().and_then(|val| val.parse::<i64>().ok()).map(|hours| hours*60*60).unwrap_or(BBUserCache::DEFAULT_TTL);
+    let mut users = BBUserCache::load(&work_dir.join("users.json"), user_cache_ttl).expect("Error loading user cache");
+    let mut hooks = Hooks::load(&work_dir.join("hooks.json")).expect("Error loading hooks");
+    let store = ContentStore::from_env(&work_dir, &out_dir).unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    });
+    #[cfg(feature = "git-snapshots")]
+    let snapshots = snapshot::SnapshotConfig::from_env().expect("Error: Invalid git snapshot configuration");
+    #[cfg(not(feature = "git-snapshots"))]
+    if std::env::var("BBCM_GIT_SNAPSHOTS").is_ok() {
+        eprintln!("Note: bbcm was built without the \"git-snapshots\" feature, so BBCM_GIT_SNAPSHOTS has no effect.");
+    }
     let mut courses: HashMap<String, BBCourse> = load_courses(&courses_json_path).into_iter().map(|course| (course.alias.clone(), course)).collect();
 
-    match Bbcm::from_args() {
-        Bbcm::Register => {
-            let course = BBCourse::register(&session, &out_dir);
+    match args.command {
+        Bbcm::Register {
+            term,
+        } => {
+            let terms = term.as_ref().map(|_| BBTerm::get_all(&session).expect("Error: Could not download terms"));
+            let term = term.map(|term| find_term(terms.as_ref().unwrap(), &term));
+            let course = BBCourse::register(&session, &out_dir, term);
             courses.insert(course.alias.clone(), course);
         },
 
-        Bbcm::Courses => {
+        Bbcm::Courses {
+            term,
+            long,
+            refresh,
+        } => {
             if courses.is_empty() {
                 println!("No courses registered yet.");
             } else {
-                for course in courses.values() {
-                    course.view();
+                if refresh {
+                    for (alias, course) in &mut courses {
+                        if let Err(err) = course.refresh_info(&session) {
+                            eprintln!("Error refreshing details for {}: {}", alias, err);
+                        }
+                    }
+                } else {
+                    refresh_missing_info(&mut courses, &session);
+                }
+                let terms = if term.is_some() || courses.values().any(|course| !course.info.term_id.is_empty()) {
+                    get_terms(&session, &courses, refresh)
+                } else {
+                    Vec::new() // Nothing to group by
+                };
+                let shown_terms = match &term {
+                    Some(term) => find_terms(&terms, term),
+                    None => terms.iter().collect(),
+                };
+                // Courses are grouped in the order the terms start, followed by courses with an unknown term
+                let mut groups: Vec<(String, Vec<&BBCourse>)> = shown_terms.iter().map(|term| {
+                    (term.heading(), courses.values().filter(|course| course.info.term_id == term.id).collect())
+                }).collect();
+                if term.is_none() {
+                    groups.push((String::from("Other"), courses.values().filter(|course| !terms.iter().any(|term| term.id == course.info.term_id)).collect()));
+                }
+                for (heading, mut term_courses) in groups.into_iter().filter(|(_, term_courses)| !term_courses.is_empty()) {
+                    term_courses.sort_by(|c1, c2| c1.alias.cmp(&c2.alias));
+                    println!("{}", heading);
+                    for course in term_courses {
+                        if long {
+                            if let Err(err) = course.view_long(&session, &work_dir) {
+                                eprintln!("Error viewing {}: {}", course.alias, err);
+                            }
+                        } else {
+                            course.view();
+                        }
+                    }
+                    println!();
                 }
             }
         },
@@ -99,39 +296,200 @@ fn main() {
             overwrite,
         } => {
             if let Some(course) = courses.get_mut(&course_alias) {
-                if let Ok(download_size) = course.download_course_content_tree(&session, overwrite) {
-                    println!("Downloaded a total of {:.1} MB.", download_size/1000000.0);
-                    course.last_tree_download = utc_now();
-                } 
+                download_tree(course, &session, store.as_ref(), overwrite, &hooks);
+                #[cfg(feature = "git-snapshots")]
+                commit_snapshot(course, &work_dir, &users, snapshots.as_ref());
             } else {
                 eprintln!("Course with alias {} not found.", course_alias);
             }
         },
 
         Bbcm::Trees {
+            term,
             overwrite,
         } => {
-            for (alias, course) in &mut courses {
+            let term_ids = term.map(|term| {
+                refresh_missing_info(&mut courses, &session);
+                let terms = BBTerm::get_all(&session).expect("Error: Could not download terms");
+                find_terms(&terms, &term).iter().map(|term| term.id.clone()).collect::<Vec<String>>()
+            });
+            for (alias, course) in courses.iter_mut().filter(|(_, course)| term_ids.as_ref().map(|ids| ids.contains(&course.info.term_id)).unwrap_or(true)) {
                 println!("Downloading tree for {}.", alias);
-                if let Ok(download_size) = course.download_course_content_tree(&session, overwrite) {
-                    println!("Downloaded a total of {:.1} MB.", download_size/1000000.0);
-                    course.last_tree_download = utc_now();
-                } 
-            } 
+                download_tree(course, &session, store.as_ref(), overwrite, &hooks);
+                #[cfg(feature = "git-snapshots")]
+                commit_snapshot(course, &work_dir, &users, snapshots.as_ref());
+            }
+        },
+
+        Bbcm::Announcements {
+            command: Some(AnnouncementsCommand::MarkRead {
+                course_alias,
+            }),
+            ..
+        } => {
+            for (alias, course) in &courses {
+                if course_alias.as_ref().map(|a| a == alias).unwrap_or(true) {
+                    let marked = course.mark_announcements_read(&work_dir).unwrap();
+                    println!("Marked {} announcements as read for {}.", marked, alias);
+                }
+            }
+            if let Some(course_alias) = course_alias.filter(|a| !courses.contains_key(a)) {
+                eprintln!("Course with alias {} not found.", course_alias);
+            }
+        },
+
+        Bbcm::Announcements {
+            course_alias,
+            new: true,
+            ..
+        } => {
+            for (alias, course) in &courses {
+                if course_alias.as_ref().map(|a| a == alias).unwrap_or(true) {
+                    println!("Viewing new announcements for {}.", alias);
+                    let (shown, new_announcements) = course.view_new_course_announcements(&session, &work_dir, &mut users).unwrap();
+                    if shown == 0 {
+                        println!("No new announcements found.");
+                    }
+                    run_announcement_hooks(&hooks, course, &new_announcements);
+                }
+            }
+            if let Some(course_alias) = course_alias.filter(|a| !courses.contains_key(a)) {
+                eprintln!("Course with alias {} not found.", course_alias);
+            }
         },
 
         Bbcm::Announcements {
             course_alias,
             limit,
             offset,
+            ..
         } => {
+            let course_alias = course_alias.expect("Course alias is required");
             if let Some(course) = courses.get(&course_alias) {
-                course.view_course_announcements(&session, limit, offset).unwrap();
+                let new_announcements = course.view_course_announcements(&session, &work_dir, &mut users, limit, offset).unwrap();
+                run_announcement_hooks(&hooks, course, &new_announcements);
             } else {
                 eprintln!("Course with alias {} not found.", course_alias);
             }
         },
 
+        Bbcm::Feed {
+            course_aliases,
+            since,
+            until,
+            search,
+        } => {
+            for course_alias in course_aliases.iter().filter(|a| !courses.contains_key(*a)) {
+                eprintln!("Course with alias {} not found.", course_alias);
+            }
+            let mut feed = Vec::new();
+            for (alias, course) in &courses {
+                if course_aliases.is_empty() || course_aliases.contains(alias) {
+                    let cache = course.update_announcement_cache(&session, &work_dir).unwrap();
+                    feed.extend(cache.announcements().into_iter().cloned().map(|announcement| (alias, announcement)));
+                }
+            }
+            feed.retain(|(_, announcement)| {
+                since.as_ref().map(|since| partial_cmp_dt(&announcement.created, since) != Some(Ordering::Less)).unwrap_or(true)
+                    && until.as_ref().map(|until| partial_cmp_dt(&announcement.created, until) == Some(Ordering::Less)).unwrap_or(true)
+                    && search.as_ref().map(|query| announcement.contains_text(query)).unwrap_or(true)
+            });
+            feed.sort_by(|(_, a1), (_, a2)| {
+                partial_cmp_dt(&a1.created, &a2.created).unwrap_or(Ordering::Equal)
+            });
+            if feed.is_empty() {
+                println!("No announcements found.");
+            } else {
+                BBAnnouncement::resolve_creators(feed.iter().map(|(_, announcement)| announcement), &session, &mut users);
+                for (alias, announcement) in &feed {
+                    announcement.view_with_course(&users, alias);
+                }
+            }
+            for (alias, course) in courses.iter().filter(|(alias, _)| course_aliases.is_empty() || course_aliases.contains(alias)) {
+                let mut cache = course.load_announcement_cache(&work_dir).unwrap();
+                let new_announcements = cache.mark_shown(feed.iter().filter(|(a, _)| *a == alias).map(|(_, announcement)| &announcement.id));
+                cache.save().unwrap();
+                run_announcement_hooks(&hooks, course, &new_announcements);
+            }
+        },
+
+        Bbcm::ExportFeeds {
+            course_aliases,
+            format,
+            feed_dir,
+        } => {
+            for course_alias in course_aliases.iter().filter(|a| !courses.contains_key(*a)) {
+                eprintln!("Course with alias {} not found.", course_alias);
+            }
+            let feed_dir = feed_dir.unwrap_or_else(|| out_dir.join("feeds"));
+            std::fs::create_dir_all(&feed_dir).expect("Error creating feed dir");
+            let caches: Vec<_> = courses.iter()
+                .filter(|(alias, _)| course_aliases.is_empty() || course_aliases.contains(alias))
+                .map(|(alias, course)| (alias, course, course.update_announcement_cache(&session, &work_dir).unwrap()))
+                .collect();
+            BBAnnouncement::resolve_creators(caches.iter().flat_map(|(_, _, cache)| cache.announcements()), &session, &mut users);
+            let mut combined_entries = Vec::new();
+            for (alias, course, cache) in &caches {
+                let entries = || cache.announcements().into_iter().map(|announcement| CourseAnnouncement {
+                    course_alias: alias,
+                    course_id: &course.id,
+                    announcement,
+                });
+                combined_entries.extend(entries());
+                let feed = AnnouncementFeed::new(
+                    &format!("urn:bbcm:course:{}", course.id),
+                    &format!("{} ({}) announcements", alias, course.course_code),
+                    &course.web_url(&session),
+                    entries().collect(),
+                );
+                let feed_path = feed_dir.join(format!("{}.{}", valid_filename(alias), format.extension()));
+                if feed.write(&users, format, &feed_path).unwrap() {
+                    println!("Wrote {:?}", feed_path);
+                }
+            }
+            let combined_feed = AnnouncementFeed::new(
+                "urn:bbcm:all",
+                "Blackboard announcements",
+                &format!("https://{}", session.domain),
+                combined_entries,
+            );
+            let combined_feed_path = feed_dir.join(format!("all.{}", format.extension()));
+            if combined_feed.write(&users, format, &combined_feed_path).unwrap() {
+                println!("Wrote {:?}", combined_feed_path);
+            }
+        },
+
+        Bbcm::ExportMail {
+            course_aliases,
+            format,
+            mail_path,
+        } => {
+            for course_alias in course_aliases.iter().filter(|a| !courses.contains_key(*a)) {
+                eprintln!("Course with alias {} not found.", course_alias);
+            }
+            let mail_path = mail_path.unwrap_or_else(|| format.default_path(&out_dir));
+            let mut mailbox = AnnouncementMailbox::open(&mail_path, format).expect("Error opening mailbox");
+            for (alias, course) in &courses {
+                if course_aliases.is_empty() || course_aliases.contains(alias) {
+                    let cache = course.update_announcement_cache(&session, &work_dir).unwrap();
+                    BBAnnouncement::resolve_creators(cache.announcements(), &session, &mut users);
+                    let mut exported = 0;
+                    for announcement in cache.announcements() {
+                        let entry = CourseAnnouncement {
+                            course_alias: alias,
+                            course_id: &course.id,
+                            announcement,
+                        };
+                        if mailbox.export(&session, &users, &entry).unwrap() {
+                            exported += 1;
+                        }
+                    }
+                    println!("Exported {} announcements for {}.", exported, alias);
+                }
+            }
+            mailbox.save().expect("Error saving mailbox state");
+        },
+
         Bbcm::Gradebooks {
             past,
         } => {
@@ -141,6 +499,492 @@ fn main() {
             } 
         },
 
+        Bbcm::Due {
+            within,
+        } => {
+            let overdue_since = utc_offset_from_now(-within.unwrap_or_else(|| Duration::days(OVERDUE_DAYS)));
+            let until = within.map(utc_offset_from_now);
+            let mut deadlines = Vec::new();
+            for (alias, course) in &courses {
+                let gradebook_columns = course.get_course_gradebook(&session).unwrap();
+                for gbc in gradebook_columns {
+                    let in_window = partial_cmp_dt(&gbc.due, &overdue_since) == Some(Ordering::Greater)
+                        && until.as_ref().map(|until| partial_cmp_dt(&gbc.due, until) == Some(Ordering::Less)).unwrap_or(true);
+                    if let Some(urgency) = gbc.urgency().filter(|_| in_window) {
+                        // Columns that aren't assignments have no attempts
+                        let submitted = course.get_column_attempts(&session, &gbc.id)
+                            .map(|attempts| attempts.iter().any(|attempt| attempt.is_submitted()))
+                            .unwrap_or(false);
+                        if urgency != Urgency::Overdue || !submitted {
+                            deadlines.push((urgency, alias, gbc, submitted));
+                        }
+                    }
+                }
+            }
+            deadlines.sort_by(|(u1, _, gbc1, _), (u2, _, gbc2, _)| {
+                u1.cmp(u2).then(partial_cmp_dt(&gbc1.due, &gbc2.due).unwrap_or(Ordering::Equal))
+            });
+            if deadlines.is_empty() {
+                println!("No deadlines found.");
+            }
+            let mut current_urgency = None;
+            for (urgency, alias, gbc, submitted) in &deadlines {
+                if current_urgency != Some(*urgency) {
+                    println!("\n{}", urgency.heading());
+                    current_urgency = Some(*urgency);
+                }
+                gbc.view_due(alias, *submitted);
+            }
+        },
+
+        Bbcm::Submissions {
+            course_alias,
+            list,
+        } => {
+            for (alias, course) in &courses {
+                if course_alias.as_ref().map(|a| a == alias).unwrap_or(true) {
+                    println!("Viewing submissions for {}.", alias);
+                    if let Ok(download_size) = course.download_course_submissions(&session, list) {
+                        if !list {
+                            println!("Downloaded a total of {:.1} MB.", download_size/1000000.0);
+                        }
+                    }
+                }
+            }
+            if let Some(course_alias) = course_alias.filter(|a| !courses.contains_key(a)) {
+                eprintln!("Course with alias {} not found.", course_alias);
+            }
+        },
+
+        Bbcm::Submit {
+            course_alias,
+            assignment,
+            files,
+            text,
+            draft,
+            dry_run,
+        } => {
+            if let Some(course) = courses.get(&course_alias) {
+                let submission = course.find_gradebook_column(&session, &assignment)
+                    .and_then(|gbc| BBSubmission::new(course, gbc, &files, text, draft));
+                match submission {
+                    Ok(submission) => {
+                        let previous_attempts = course.get_column_attempts(&session, &submission.column.id).unwrap_or_default();
+                        submission.preview(&previous_attempts);
+                        if dry_run {
+                            println!("Dry run; nothing was sent.");
+                        } else {
+                            println!("Proceed? [y/N]");
+                            if stdin_trimmed_line().to_lowercase() == "y" {
+                                match submission.submit(&session) {
+                                    Ok(attempt_id) => println!("Created attempt {}.", attempt_id),
+                                    Err(err) => eprintln!("Error submitting: {}", err),
+                                }
+                            } else {
+                                println!("Cancelled.");
+                            }
+                        }
+                    },
+                    Err(err) => eprintln!("{}", err),
+                }
+            } else {
+                eprintln!("Course with alias {} not found.", course_alias);
+            }
+        },
+
+        Bbcm::Grades {
+            course_alias,
+        } => {
+            let me = BBUser::get_current(&session).expect("Error getting current user");
+            for (alias, course) in &courses {
+                if course_alias.as_ref().map(|a| a == alias).unwrap_or(true) {
+                    println!("Viewing grades for {}.", alias);
+                    course.view_course_grades(&session, &me.id).unwrap();
+                }
+            }
+            if let Some(course_alias) = course_alias.filter(|a| !courses.contains_key(a)) {
+                eprintln!("Course with alias {} not found.", course_alias);
+            }
+        },
+
+        Bbcm::Ics {
+            course_aliases,
+            alarm,
+            ics_path,
+        } => {
+            for course_alias in course_aliases.iter().filter(|a| !courses.contains_key(*a)) {
+                eprintln!("Course with alias {} not found.", course_alias);
+            }
+            let ics_path = ics_path.unwrap_or_else(|| out_dir.join("bbcm.ics"));
+            let since = utc_offset_from_now(Duration::weeks(-CALENDAR_WEEKS_BEFORE));
+            let until = utc_offset_from_now(Duration::weeks(CALENDAR_WEEKS_AFTER));
+            let mut calendar = ICalendar::new("Blackboard", &alarm);
+            for (alias, course) in &courses {
+                if course_aliases.is_empty() || course_aliases.contains(alias) {
+                    let gradebook_columns = course.get_course_gradebook(&session).unwrap();
+                    calendar.events.extend(gradebook_columns.iter()
+                        .filter(|gbc| gbc.due != "null")
+                        .map(|gbc| ICalEvent::from_gradebook_column(&session, course, gbc)));
+                    match course.get_course_calendar_items(&session, &since, &until) {
+                        Ok(items) => {
+                            // Gradebook column items are already covered by the deadlines above
+                            calendar.events.extend(items.iter()
+                                .filter(|item| item.item_type != "GradebookColumn")
+                                .map(|item| ICalEvent::from_calendar_item(&session, course, item)));
+                        },
+                        Err(err) => eprintln!("Error downloading calendar items for {}: {}", alias, err),
+                    }
+                }
+            }
+            calendar.write(&ics_path).expect("Error writing calendar");
+            println!("Wrote {} events to {:?}", calendar.events.len(), ics_path);
+        },
+
+        Bbcm::People {
+            course_alias,
+            role,
+            csv,
+            vcard,
+        } => {
+            if let Some(course) = courses.get(&course_alias) {
+                let role_ids: Vec<String> = role.iter().map(|r| BBMembership::role_id(r)).collect();
+                let mut memberships = course.get_course_memberships(&session, None).unwrap();
+                memberships.retain(|membership| role_ids.is_empty() || role_ids.contains(&membership.role));
+                memberships.sort_by(|m1, m2| m1.role.cmp(&m2.role).then(m1.user.name.cmp(&m2.user.name)));
+                if memberships.is_empty() {
+                    println!("No members found.");
+                }
+                for membership in &memberships {
+                    membership.view();
+                }
+                if let Some(csv) = csv {
+                    roster::write_csv(&memberships, &csv).expect("Error writing CSV file");
+                    println!("Wrote {:?}", csv);
+                }
+                if let Some(vcard) = vcard {
+                    roster::write_vcard(&memberships, &format!("{} ({})", course.course_code, course_alias), &vcard).expect("Error writing vCard file");
+                    println!("Wrote {:?}", vcard);
+                }
+            } else {
+                eprintln!("Course with alias {} not found.", course_alias);
+            }
+        },
+
+        Bbcm::Groups {
+            course_alias,
+            all,
+            members,
+        } => {
+            if let Some(course) = courses.get(&course_alias) {
+                let groups = if all {
+                    course.get_course_groups(&session)
+                } else {
+                    BBUser::get_current(&session).and_then(|me| course.get_my_groups(&session, &me.id))
+                }.unwrap();
+                if groups.is_empty() {
+                    println!("No groups found.");
+                }
+                for group in &groups {
+                    group.view();
+                    if members {
+                        let member_ids = course.get_group_member_ids(&session, &group.id).unwrap_or_default();
+                        let member_ids: Vec<&str> = member_ids.iter().map(|id| id.as_str()).collect();
+                        users.resolve(&session, &member_ids);
+                        for member_id in member_ids {
+                            println!("    {}", users.display_name(member_id));
+                        }
+                    }
+                }
+            } else {
+                eprintln!("Course with alias {} not found.", course_alias);
+            }
+        },
+
+        Bbcm::Discussions {
+            course_alias,
+            format,
+        } => {
+            for (alias, course) in &courses {
+                if course_alias.as_ref().map(|a| a == alias).unwrap_or(true) {
+                    match course.sync_discussions(&session, &work_dir, &mut users, format) {
+                        Ok(updated_threads) => println!("Updated {} discussion threads for {}.", updated_threads, alias),
+                        Err(err) => eprintln!("Error syncing discussions for {}: {}", alias, err),
+                    }
+                }
+            }
+            if let Some(course_alias) = course_alias.filter(|a| !courses.contains_key(a)) {
+                eprintln!("Course with alias {} not found.", course_alias);
+            }
+        },
+
+        Bbcm::Watch {
+            interval,
+            hooks: hook_commands,
+            notify,
+        } => {
+            let me = BBUser::get_current(&session).expect("Error: Could not look up current user");
+            #[cfg(feature = "notifications")]
+            let notifier = if notify {
+                match notifier::dbus::DbusNotificationServer::session() {
+                    Ok(server) => {
+                        let notifier = notifier::Notifier::new(server);
+                        notifier.spawn_action_listener();
+                        Some(notifier)
+                    },
+                    Err(err) => {
+                        eprintln!("Error connecting to the session bus, notifications are disabled: {}", err);
+                        None
+                    },
+                }
+            } else {
+                None
+            };
+            #[cfg(not(feature = "notifications"))]
+            if notify {
+                eprintln!("Note: bbcm was built without the \"notifications\" feature, so --notify has no effect.");
+            }
+            // The session is reused between rounds, and state is saved after each round since the loop only ends when killed
+            loop {
+                for (alias, course) in &mut courses {
+                    match watch::sync_course(course, &session, store.as_ref(), &work_dir, &me.id) {
+                        Ok(events) => {
+                            for event in &events {
+                                watch::emit(event, &hook_commands);
+                                hooks.run(event);
+                            }
+                            #[cfg(feature = "notifications")]
+                            if let Some(notifier) = &notifier {
+                                notifier.notify_events(&events, course.out_dir());
+                            }
+                            #[cfg(feature = "git-snapshots")]
+                            commit_snapshot(course, &work_dir, &users, snapshots.as_ref());
+                        },
+                        Err(err) => eprintln!("Error syncing {}: {}", alias, err),
+                    }
+                }
+                save_courses(courses.values(), &courses_json_path);
+                users.save().expect("Error saving user cache");
+                std::thread::sleep(interval.to_std().expect("Error: Invalid interval"));
+            }
+        },
+
+        Bbcm::Index {
+            course_alias,
+            rebuild,
+        } => {
+            let mut index = SearchIndex::load(&work_dir.join("search_index.json")).expect("Error loading search index");
+            if rebuild {
+                index.clear();
+            }
+            update_search_index(&mut index, &courses, course_alias.as_ref(), &work_dir);
+            index.save().expect("Error saving search index");
+            println!("{} documents in the search index.", index.document_count());
+            if let Some(course_alias) = course_alias.filter(|a| !courses.contains_key(a)) {
+                eprintln!("Course with alias {} not found.", course_alias);
+            }
+        },
+
+        Bbcm::Search {
+            query,
+            course,
+            limit,
+        } => {
+            let mut index = SearchIndex::load(&work_dir.join("search_index.json")).expect("Error loading search index");
+            update_search_index(&mut index, &courses, course.as_ref(), &work_dir);
+            index.save().expect("Error saving search index");
+            let hits = index.search(&query.join(" "), course.as_deref(), limit);
+            if hits.is_empty() {
+                println!("No results found.");
+            }
+            for (rank, hit) in hits.iter().enumerate() {
+                hit.view(rank + 1);
+            }
+        },
+
+        Bbcm::Serve {
+            port,
+        } => {
+            let mut index = SearchIndex::load(&work_dir.join("search_index.json")).expect("Error loading search index");
+            update_search_index(&mut index, &courses, None, &work_dir);
+            index.save().expect("Error saving search index");
+            let server = CourseServer {
+                session: &session,
+                courses: &courses,
+                users: &users,
+                work_dir: &work_dir,
+                calendar_path: out_dir.join("bbcm.ics"),
+                index,
+            };
+            if let Err(err) = server.run(&format!("127.0.0.1:{}", port)) {
+                eprintln!("Error serving courses: {}", err);
+            }
+        },
+
+        Bbcm::Hooks {
+            command,
+        } => {
+            match command {
+                HooksCommand::List => {
+                    if hooks.hooks.is_empty() {
+                        println!("No hooks configured.");
+                    }
+                    for (index, hook) in hooks.hooks.iter().enumerate() {
+                        hook.view(index);
+                    }
+                },
+                HooksCommand::Add {
+                    trigger,
+                    command,
+                    course,
+                    timeout,
+                } => {
+                    if let Some(course_alias) = course.as_ref().filter(|a| !courses.contains_key(*a)) {
+                        eprintln!("Course with alias {} not found.", course_alias);
+                    } else {
+                        hooks.hooks.push(Hook {
+                            command,
+                            trigger,
+                            course_alias: course,
+                            timeout: timeout.to_std().expect("Error: Invalid timeout"),
+                        });
+                        hooks.save().expect("Error saving hooks");
+                    }
+                },
+                HooksCommand::Remove {
+                    index,
+                } => {
+                    if index < hooks.hooks.len() {
+                        hooks.hooks.remove(index);
+                        hooks.save().expect("Error saving hooks");
+                    } else {
+                        eprintln!("Hook {} not found.", index);
+                    }
+                },
+            }
+        },
+
+        Bbcm::Archive {
+            term,
+            force,
+        } => {
+            refresh_missing_info(&mut courses, &session);
+            let terms = BBTerm::get_all(&session).expect("Error: Could not download terms");
+            for term in find_terms(&terms, &term) {
+                if !term.is_finished() && !force {
+                    eprintln!("Term {} hasn't ended yet (use --force to archive it anyway).", term.name);
+                    continue;
+                }
+                let archive_dir = out_dir.join("archive").join(valid_dir_name(&term.name));
+                let mut aliases: Vec<String> = courses.values().filter(|course| course.info.term_id == term.id).map(|course| course.alias.clone()).collect();
+                aliases.sort();
+                if aliases.is_empty() {
+                    println!("No registered courses in {}.", term.name);
+                }
+                for alias in aliases {
+                    match courses[&alias].archive(&archive_dir) {
+                        Ok(archive_path) => {
+                            println!("Archived {} to {}.", alias, archive_path.display());
+                            courses.remove(&alias);
+                        },
+                        Err(err) => eprintln!("Error archiving {}: {}", alias, err),
+                    }
+                }
+            }
+        },
+
+        Bbcm::Dedupe {
+            course_alias,
+            dry_run,
+        } => {
+            let store = match &store {
+                Some(store) => store,
+                None => {
+                    eprintln!("The content store is off, set BBCM_STORE_MODE to hardlink, symlink or copy to use it.");
+                    std::process::exit(1);
+                },
+            };
+            let mut aliases: Vec<&String> = courses.keys().filter(|alias| course_alias.as_ref().map(|a| a == *alias).unwrap_or(true)).collect();
+            aliases.sort();
+            let mut total = StoreReport::default();
+            for alias in aliases {
+                let paths = match courses[alias].load_manifest() {
+                    Ok(manifest) => manifest.attachment_paths(),
+                    Err(err) => {
+                        eprintln!("Error loading manifest for {}: {}", alias, err);
+                        continue;
+                    },
+                };
+                let report = if dry_run {
+                    store.analyse(&paths)
+                } else {
+                    paths.iter().try_fold(StoreReport::default(), |mut report, path| {
+                        report.merge(store.add(path)?);
+                        Ok(report)
+                    })
+                };
+                match report {
+                    Ok(report) => {
+                        println!("{}: {} duplicates of {} files ({:.1} MB)", alias, report.duplicates, report.files, report.reclaimed as f64/1000000.0);
+                        total.merge(report);
+                    },
+                    Err(err) => eprintln!("Error deduplicating {}: {}", alias, err),
+                }
+            }
+            if dry_run {
+                println!("{:.1} MB can be reclaimed by {} {} links.", total.reclaimed as f64/1000000.0, total.duplicates, store.mode.as_str());
+            } else {
+                println!("Reclaimed {:.1} MB by {} {} links.", total.reclaimed as f64/1000000.0, total.duplicates, store.mode.as_str());
+                match store.prune() {
+                    Ok((0, _)) => {},
+                    Ok((removed, size)) => println!("Removed {} unused files ({:.1} MB) from the content store.", removed, size as f64/1000000.0),
+                    Err(err) => eprintln!("Error pruning the content store: {}", err),
+                }
+            }
+            if let Some(course_alias) = course_alias.filter(|a| !courses.contains_key(a)) {
+                eprintln!("Course with alias {} not found.", course_alias);
+            }
+        },
+
+        Bbcm::History {
+            path,
+            restore,
+        } => {
+            // Paths are matched against the course folders once symlinks and relative parts are resolved
+            let absolute_path = match (path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or_else(|| Path::new(".")).canonicalize(), path.file_name()) {
+                (Ok(parent), Some(file_name)) => parent.join(file_name),
+                _ => {
+                    eprintln!("{} not found.", path.display());
+                    std::process::exit(1);
+                },
+            };
+            let course = courses.values().find(|course| course.out_dir().canonicalize().map(|out_dir| absolute_path.starts_with(out_dir)).unwrap_or(false));
+            let course = match course {
+                Some(course) => course,
+                None => {
+                    eprintln!("{} isn't in the folder of a registered course.", path.display());
+                    std::process::exit(1);
+                },
+            };
+            let mut history = course.load_history().expect("Error loading history");
+            let path = course.out_dir().join(absolute_path.strip_prefix(course.out_dir().canonicalize().unwrap()).unwrap());
+            match restore {
+                Some(number) => {
+                    history.restore(&path, number).expect("Error restoring version");
+                    history.save().expect("Error saving history");
+                    println!("Restored v{} of {}.", number, path.display());
+                },
+                None => match history.versions.get(&history.relative_path(&path)) {
+                    Some(versions) => {
+                        println!("[{}] {}", course.alias, history.relative_path(&path).display());
+                        for version in versions {
+                            version.view();
+                        }
+                    },
+                    None => println!("No earlier versions of {}.", path.display()),
+                },
+            }
+        },
+
         Bbcm::Remove {
             course_alias,
         } => {
@@ -154,5 +998,6 @@ fn main() {
         }
     }
 
-    save_courses(&courses.into_iter().map(|t| t.1).collect::<Vec<BBCourse>>(), &courses_json_path);
+    save_courses(courses.values(), &courses_json_path);
+    users.save().expect("Error saving user cache");
 }
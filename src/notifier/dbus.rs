@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use zbus::blocking::{Connection, MessageIterator};
+use zbus::zvariant::Value;
+use super::NotificationServer;
+
+// org.freedesktop.Notifications on the D-Bus session bus
+pub struct DbusNotificationServer {
+    connection: Connection,
+    actions: Mutex<MessageIterator>,
+}
+
+impl DbusNotificationServer {
+    const DESTINATION: &'static str = "org.freedesktop.Notifications";
+    const PATH: &'static str = "/org/freedesktop/Notifications";
+    const APP_NAME: &'static str = "bbcm";
+
+    pub fn session() -> Result<DbusNotificationServer, Box<dyn std::error::Error>> {
+        let connection = Connection::session()?;
+        // Subscribed up front, so that clicks on the first notifications aren't missed
+        let rule = zbus::MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .interface(DbusNotificationServer::DESTINATION)?
+            .member("ActionInvoked")?
+            .build();
+        let actions = MessageIterator::for_match_rule(rule, &connection, None)?;
+        Ok(DbusNotificationServer {
+            connection,
+            actions: Mutex::new(actions),
+        })
+    }
+}
+
+impl NotificationServer for DbusNotificationServer {
+    fn notify(&self, summary: &str, body: &str, actions: &[&str]) -> Result<u32, Box<dyn std::error::Error>> {
+        let hints: HashMap<&str, Value> = HashMap::new();
+        let reply = self.connection.call_method(
+            Some(DbusNotificationServer::DESTINATION),
+            DbusNotificationServer::PATH,
+            Some(DbusNotificationServer::DESTINATION),
+            "Notify",
+            &(DbusNotificationServer::APP_NAME, 0u32, "", summary, body, actions, hints, -1i32),
+        )?;
+        Ok(reply.body().deserialize::<u32>()?)
+    }
+
+    fn next_action(&self) -> Result<(u32, String), Box<dyn std::error::Error>> {
+        let mut actions = self.actions.lock().expect("Notification action stream poisoned");
+        match actions.next() {
+            Some(message) => Ok(message?.body().deserialize::<(u32, String)>()?),
+            None => Err("Session bus connection closed".into()),
+        }
+    }
+}
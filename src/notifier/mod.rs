@@ -0,0 +1,216 @@
+pub mod dbus;
+
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use crate::watch::{WatchEvent, WatchEventKind};
+
+// The parts of org.freedesktop.Notifications the notifier uses, so that the session bus can be replaced by a mock
+pub trait NotificationServer: Send + Sync + 'static {
+    // Shows a notification and returns its id
+    fn notify(&self, summary: &str, body: &str, actions: &[&str]) -> Result<u32, Box<dyn std::error::Error>>;
+
+    // Blocks until a notification action is invoked, and returns the notification id and action key
+    fn next_action(&self) -> Result<(u32, String), Box<dyn std::error::Error>>;
+}
+
+// Desktop notifications for new files, announcements and grades.
+// Clicking a notification opens the file, or the course folder when a notification covers several changes.
+pub struct Notifier<S: NotificationServer> {
+    server: Arc<S>,
+    targets: Arc<Mutex<HashMap<u32, PathBuf>>>,
+}
+
+impl<S: NotificationServer> Notifier<S> {
+    const DEFAULT_ACTION: &'static str = "default";
+
+    pub fn new(server: S) -> Notifier<S> {
+        Notifier {
+            server: Arc::new(server),
+            targets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Changes of the same kind in one sync are summarized in a single notification. Baseline events were already there before watching.
+    pub fn notify_events(&self, events: &[WatchEvent], course_dir: &Path) {
+        for kind in &[WatchEventKind::NewFile, WatchEventKind::NewAnnouncement, WatchEventKind::NewGrade] {
+            let matching_events: Vec<&WatchEvent> = events.iter().filter(|event| event.kind == *kind && !event.baseline).collect();
+            if let Some(notification) = Notifier::<S>::notification(&matching_events, course_dir) {
+                if let Err(err) = self.send(notification) {
+                    eprintln!("Error sending notification: {}", err);
+                }
+            }
+        }
+    }
+
+    fn notification(events: &[&WatchEvent], course_dir: &Path) -> Option<(String, String, PathBuf)> {
+        let first = events.first()?;
+        let summary = match (first.kind, events.len()) {
+            (WatchEventKind::NewFile, 1) => format!("New file in {}", first.course_alias),
+            (WatchEventKind::NewFile, count) => format!("{} new files in {}", count, first.course_alias),
+            (WatchEventKind::NewAnnouncement, 1) => format!("New announcement in {}", first.course_alias),
+            (WatchEventKind::NewAnnouncement, count) => format!("{} new announcements in {}", count, first.course_alias),
+            (WatchEventKind::NewGrade, 1) => format!("New grade in {}", first.course_alias),
+            (WatchEventKind::NewGrade, count) => format!("{} new grades in {}", count, first.course_alias),
+            _ => return None,
+        };
+        let body = events.iter().map(|event| match &event.grade {
+            Some(grade) => format!("{}: {}", event.title, grade),
+            None => event.title.clone(),
+        }).collect::<Vec<String>>().join("\n");
+        let target = match (first.kind, &first.path) {
+            (WatchEventKind::NewFile, Some(path)) if events.len() == 1 => path.clone(),
+            _ => course_dir.to_path_buf(),
+        };
+        Some((summary, body, target))
+    }
+
+    fn send(&self, (summary, body, target): (String, String, PathBuf)) -> Result<(), Box<dyn std::error::Error>> {
+        let id = self.server.notify(&summary, &body, &[Notifier::<S>::DEFAULT_ACTION, "Open"])?;
+        self.targets.lock().expect("Notification targets poisoned").insert(id, target);
+        Ok(())
+    }
+
+    // The path to open for an invoked action, if it belongs to one of our notifications
+    pub fn target(&self, id: u32, action: &str) -> Option<PathBuf> {
+        if action != Notifier::<S>::DEFAULT_ACTION {
+            return None;
+        }
+        self.targets.lock().expect("Notification targets poisoned").get(&id).cloned()
+    }
+
+    // Opens the targets of clicked notifications until the connection fails
+    pub fn spawn_action_listener(&self) -> std::thread::JoinHandle<()> {
+        let listener = Notifier {
+            server: self.server.clone(),
+            targets: self.targets.clone(),
+        };
+        std::thread::spawn(move || {
+            loop {
+                match listener.server.next_action() {
+                    Ok((id, action)) => {
+                        if let Some(path) = listener.target(id, &action) {
+                            if let Err(err) = open_path(&path) {
+                                eprintln!("Error opening {}: {}", path.display(), err);
+                            }
+                        }
+                    },
+                    Err(err) => {
+                        eprintln!("Error listening for notification actions: {}", err);
+                        break;
+                    },
+                }
+            }
+        })
+    }
+}
+
+fn open_path(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    std::process::Command::new("xdg-open").arg(path).spawn()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Records notifications instead of sending them over the session bus
+    #[derive(Default)]
+    struct MockServer {
+        sent: Mutex<Vec<(String, String, Vec<String>)>>,
+    }
+
+    impl NotificationServer for MockServer {
+        fn notify(&self, summary: &str, body: &str, actions: &[&str]) -> Result<u32, Box<dyn std::error::Error>> {
+            let mut sent = self.sent.lock().unwrap();
+            sent.push((summary.to_string(), body.to_string(), actions.iter().map(|action| action.to_string()).collect()));
+            Ok(sent.len() as u32)
+        }
+
+        fn next_action(&self) -> Result<(u32, String), Box<dyn std::error::Error>> {
+            Err("No actions in the mock".into())
+        }
+    }
+
+    fn event(kind: WatchEventKind, title: &str, path: Option<&str>, grade: Option<&str>) -> WatchEvent {
+        WatchEvent {
+            kind,
+            course_alias: String::from("ma"),
+            title: title.to_string(),
+            content_id: String::from("_1_1"),
+            path: path.map(PathBuf::from),
+            mimetype: None,
+            due: None,
+            grade: grade.map(str::to_string),
+            time: String::from("2026-10-19T10:00:00.000Z"),
+            baseline: false,
+        }
+    }
+
+    fn sent(notifier: &Notifier<MockServer>) -> Vec<(String, String, Vec<String>)> {
+        notifier.server.sent.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn groups_events_by_kind() {
+        let notifier = Notifier::new(MockServer::default());
+        notifier.notify_events(&[
+            event(WatchEventKind::NewFile, "a.pdf", Some("/c/a.pdf"), None),
+            event(WatchEventKind::NewAnnouncement, "Exam date", None, None),
+            event(WatchEventKind::NewFile, "b.pdf", Some("/c/b.pdf"), None),
+            event(WatchEventKind::UpdatedFile, "c.pdf", Some("/c/c.pdf"), None),
+            event(WatchEventKind::CourseSynced, "Mathematics", Some("/c"), None),
+        ], Path::new("/c"));
+        let summaries: Vec<String> = sent(&notifier).into_iter().map(|(summary, _, _)| summary).collect();
+        assert_eq!(summaries, vec!["2 new files in ma", "New announcement in ma"]);
+    }
+
+    #[test]
+    fn skips_baseline_events() {
+        let notifier = Notifier::new(MockServer::default());
+        let mut baseline = event(WatchEventKind::NewFile, "a.pdf", Some("/c/a.pdf"), None);
+        baseline.baseline = true;
+        notifier.notify_events(&[baseline], Path::new("/c"));
+        assert!(sent(&notifier).is_empty());
+    }
+
+    #[test]
+    fn summary_and_body() {
+        let notifier = Notifier::new(MockServer::default());
+        notifier.notify_events(&[
+            event(WatchEventKind::NewGrade, "Exercise 1", None, Some("8/10")),
+            event(WatchEventKind::NewGrade, "Exercise 2", None, Some("A")),
+        ], Path::new("/c"));
+        notifier.notify_events(&[event(WatchEventKind::NewFile, "a.pdf", Some("/c/a.pdf"), None)], Path::new("/c"));
+        let sent = sent(&notifier);
+        assert_eq!(sent[0].0, "2 new grades in ma");
+        assert_eq!(sent[0].1, "Exercise 1: 8/10\nExercise 2: A");
+        assert_eq!(sent[1].0, "New file in ma");
+        assert_eq!(sent[1].1, "a.pdf");
+        assert_eq!(sent[1].2, vec!["default", "Open"]);
+    }
+
+    #[test]
+    fn targets_file_or_course_dir() {
+        let notifier = Notifier::new(MockServer::default());
+        notifier.notify_events(&[event(WatchEventKind::NewFile, "a.pdf", Some("/c/a.pdf"), None)], Path::new("/c"));
+        notifier.notify_events(&[
+            event(WatchEventKind::NewFile, "a.pdf", Some("/c/a.pdf"), None),
+            event(WatchEventKind::NewFile, "b.pdf", Some("/c/b.pdf"), None),
+        ], Path::new("/c"));
+        notifier.notify_events(&[event(WatchEventKind::NewAnnouncement, "Exam date", None, None)], Path::new("/c"));
+        assert_eq!(notifier.target(1, "default"), Some(PathBuf::from("/c/a.pdf")));
+        assert_eq!(notifier.target(2, "default"), Some(PathBuf::from("/c")));
+        assert_eq!(notifier.target(3, "default"), Some(PathBuf::from("/c")));
+        assert_eq!(notifier.target(4, "default"), None);
+    }
+
+    #[test]
+    fn ignores_other_actions() {
+        let notifier = Notifier::new(MockServer::default());
+        notifier.notify_events(&[event(WatchEventKind::NewFile, "a.pdf", Some("/c/a.pdf"), None)], Path::new("/c"));
+        assert_eq!(notifier.target(1, "Open"), None);
+        assert_eq!(notifier.target(1, "dismiss"), None);
+        assert_eq!(notifier.target(1, "default"), Some(PathBuf::from("/c/a.pdf")));
+    }
+}
@@ -0,0 +1,2 @@
+pub mod search_index;
+pub mod text_extraction;
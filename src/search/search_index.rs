@@ -0,0 +1,418 @@
+use std::path::{Path, PathBuf};
+use std::io::{Read, Write};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use sha2::{Digest, Sha256};
+use crate::bb_course::BBCourse;
+use crate::bb_course::bb_manifest::BBManifestFile;
+use super::text_extraction::{extract_text, html_to_text};
+
+pub struct SearchDocument {
+    pub key: String, // Local path for files, announcement id for announcements
+    pub course_alias: String,
+    pub kind: String,
+    pub title: String,
+    pub path: Option<PathBuf>,
+    pub stamp: String, // When the file was synced, or when the announcement was modified
+    pub length: usize,
+    terms: Vec<(String, u32)>, // Term frequencies, which are saved as the postings
+}
+
+pub struct SearchHit<'a> {
+    pub document: &'a SearchDocument,
+    pub score: f64,
+    pub snippet: String,
+}
+
+// Inverted index over the text of synced files and cached announcements.
+// Files are only read again when the sync manifest shows they have been downloaded since they were indexed.
+// The extracted text is kept in a file per document next to the index, and only read for the snippets of search hits.
+pub struct SearchIndex {
+    path: PathBuf,
+    text_dir: PathBuf,
+    documents: Vec<SearchDocument>,
+    postings: HashMap<String, Vec<(usize, u32)>>, // Term -> (document index, term frequency)
+    changed: bool,
+}
+
+impl SearchIndex {
+    const SNIPPET_WORDS_BEFORE: usize = 10;
+    const SNIPPET_WORDS_AFTER: usize = 20;
+    const TITLE_WEIGHT: f64 = 2.0;
+
+    pub fn load(path: &Path) -> Result<SearchIndex, Box<dyn std::error::Error>> {
+        let mut documents: Vec<SearchDocument> = Vec::new();
+        let mut postings = HashMap::new();
+        let mut index = SearchIndex {
+            path: path.to_path_buf(),
+            text_dir: path.with_file_name("search_text"),
+            documents: Vec::new(),
+            postings: HashMap::new(),
+            changed: false,
+        };
+        if path.exists() {
+            let mut json_string = String::new();
+            std::fs::File::open(path)?.read_to_string(&mut json_string)?;
+            let parsed_json = json::parse(&json_string)?;
+            for member in parsed_json["documents"].members() {
+                // Indexes written by earlier versions kept the text in the index itself
+                if let Some(text) = member["text"].as_str() {
+                    std::fs::create_dir_all(&index.text_dir)?;
+                    std::fs::write(index.text_path(&member["key"].to_string()), text)?;
+                    index.changed = true;
+                }
+                documents.push(SearchDocument {
+                    key: member["key"].to_string(),
+                    course_alias: member["course"].to_string(),
+                    kind: member["kind"].to_string(),
+                    title: member["title"].to_string(),
+                    path: member["path"].as_str().map(PathBuf::from),
+                    stamp: member["stamp"].to_string(),
+                    length: member["length"].as_usize().unwrap_or(0),
+                    terms: Vec::new(),
+                });
+            }
+            for (term, entries) in parsed_json["postings"].entries() {
+                let term_postings: Vec<(usize, u32)> = entries.members().map(|entry| {
+                    (entry[0].as_usize().unwrap_or(0), entry[1].as_u32().unwrap_or(0))
+                }).collect();
+                for (index, frequency) in &term_postings {
+                    if let Some(document) = documents.get_mut(*index) {
+                        document.terms.push((term.to_string(), *frequency));
+                    }
+                }
+                postings.insert(term.to_string(), term_postings);
+            }
+        }
+        index.documents = documents;
+        index.postings = postings;
+        Ok(index)
+    }
+
+    // Only written when documents were added or removed since the index was loaded
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.changed {
+            return Ok(());
+        }
+        let mut postings = json::JsonValue::new_object();
+        for (term, term_postings) in &self.postings {
+            postings[term.as_str()] = json::JsonValue::Array(term_postings.iter().map(|(index, frequency)| json::array![*index, *frequency]).collect());
+        }
+        let index = json::object!{
+            documents: json::JsonValue::Array(self.documents.iter().map(|document| {
+                json::object!{
+                    key: document.key.clone(),
+                    course: document.course_alias.clone(),
+                    kind: document.kind.clone(),
+                    title: document.title.clone(),
+                    path: document.path.as_ref().map(|path| path.to_string_lossy().to_string()),
+                    stamp: document.stamp.clone(),
+                    length: document.length,
+                }
+            }).collect()),
+            postings: postings,
+        };
+        let mut index_file = std::fs::File::create(&self.path)?;
+        index_file.write_all(index.dump().as_bytes())?;
+        Ok(())
+    }
+
+    pub fn document_count(&self) -> usize {
+        self.documents.len()
+    }
+
+    // Lowercased words of at least two characters
+    fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.chars().count() >= 2)
+            .map(|word| word.to_lowercase())
+    }
+
+    fn text_path(&self, key: &str) -> PathBuf {
+        let hash: String = Sha256::digest(key.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect();
+        self.text_dir.join(format!("{}.txt", hash))
+    }
+
+    fn read_text(&self, document: &SearchDocument) -> String {
+        std::fs::read_to_string(self.text_path(&document.key)).unwrap_or_default()
+    }
+
+    // Counts the terms of a new document, and stores its text for snippets
+    fn index_text(&self, mut document: SearchDocument, text: &str) -> Result<SearchDocument, Box<dyn std::error::Error>> {
+        let mut frequencies: BTreeMap<String, u32> = BTreeMap::new();
+        for term in SearchIndex::tokenize(&document.title).chain(SearchIndex::tokenize(text)) {
+            *frequencies.entry(term).or_insert(0) += 1;
+        }
+        document.length = frequencies.values().sum::<u32>() as usize;
+        document.terms = frequencies.into_iter().collect();
+        std::fs::create_dir_all(&self.text_dir)?;
+        std::fs::write(self.text_path(&document.key), text)?;
+        Ok(document)
+    }
+
+    fn remove_texts<'a>(&self, documents: impl Iterator<Item = &'a SearchDocument>) {
+        for document in documents {
+            let _ = std::fs::remove_file(self.text_path(&document.key));
+        }
+    }
+
+    // Brings the documents of a course up to date with its sync manifest and announcement cache.
+    // Returns the number of documents that were (re)indexed and removed.
+    pub fn update_course(&mut self, course: &BBCourse, work_dir: &Path) -> Result<(usize, usize), Box<dyn std::error::Error>> {
+        let mut existing: HashMap<String, SearchDocument> = HashMap::new();
+        let mut other_documents = Vec::new();
+        for document in self.documents.drain(..) {
+            if document.course_alias == course.alias {
+                existing.insert(document.key.clone(), document);
+            } else {
+                other_documents.push(document);
+            }
+        }
+        let mut course_documents = Vec::new();
+        let mut indexed = 0;
+
+        let manifest = course.load_manifest()?;
+        for file in manifest.files.values().filter(|file| file.kind != BBManifestFile::LINK) {
+            // Unzipped attachments are recorded as their folder
+            for path in SearchIndex::files_below(&manifest.absolute_path(&file.path)) {
+                let key = path.to_string_lossy().to_string();
+                match existing.remove(&key).filter(|document| document.stamp == file.synced) {
+                    Some(document) => course_documents.push(document),
+                    None => {
+                        let title = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+                        // Files without extractable text are kept, so that they aren't read again on every update
+                        let text = extract_text(&path).unwrap_or_default();
+                        course_documents.push(self.index_text(SearchDocument {
+                            key,
+                            course_alias: course.alias.clone(),
+                            kind: file.kind.clone(),
+                            title,
+                            path: Some(path),
+                            stamp: file.synced.clone(),
+                            length: 0,
+                            terms: Vec::new(),
+                        }, &text)?);
+                        indexed += 1;
+                    },
+                }
+            }
+        }
+
+        for announcement in course.load_announcement_cache(work_dir)?.announcements() {
+            let key = format!("announcement:{}", announcement.id);
+            match existing.remove(&key).filter(|document| document.stamp == announcement.modified) {
+                Some(document) => course_documents.push(document),
+                None => {
+                    course_documents.push(self.index_text(SearchDocument {
+                        key,
+                        course_alias: course.alias.clone(),
+                        kind: String::from("announcement"),
+                        title: announcement.title.clone(),
+                        path: None,
+                        stamp: announcement.modified.clone(),
+                        length: 0,
+                        terms: Vec::new(),
+                    }, &html_to_text(&announcement.body))?);
+                    indexed += 1;
+                },
+            }
+        }
+
+        self.remove_texts(existing.values());
+        self.documents = other_documents;
+        self.documents.extend(course_documents);
+        if indexed > 0 || !existing.is_empty() {
+            self.rebuild_postings();
+        }
+        Ok((indexed, existing.len()))
+    }
+
+    // Drops the documents of courses that are no longer registered
+    pub fn retain_courses(&mut self, course_aliases: &HashSet<&String>) -> usize {
+        let (kept, removed): (Vec<SearchDocument>, Vec<SearchDocument>) = self.documents.drain(..)
+            .partition(|document| course_aliases.contains(&document.course_alias));
+        self.documents = kept;
+        if !removed.is_empty() {
+            self.remove_texts(removed.iter());
+            self.rebuild_postings();
+        }
+        removed.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.documents.clear();
+        self.postings.clear();
+        let _ = std::fs::remove_dir_all(&self.text_dir);
+        self.changed = true;
+    }
+
+    fn files_below(path: &Path) -> Vec<PathBuf> {
+        if path.is_dir() {
+            std::fs::read_dir(path)
+                .map(|entries| entries.filter_map(|entry| entry.ok()).flat_map(|entry| SearchIndex::files_below(&entry.path())).collect())
+                .unwrap_or_default()
+        } else if path.exists() {
+            vec![path.to_path_buf()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn rebuild_postings(&mut self) {
+        self.documents.sort_by(|d1, d2| d1.key.cmp(&d2.key));
+        self.postings.clear();
+        for (index, document) in self.documents.iter().enumerate() {
+            for (term, frequency) in &document.terms {
+                self.postings.entry(term.clone()).or_default().push((index, *frequency));
+            }
+        }
+        self.changed = true;
+    }
+
+    // Documents ranked by TF-IDF over the query terms, with extra weight for terms in the title
+    pub fn search(&self, query: &str, course_alias: Option<&str>, limit: usize) -> Vec<SearchHit<'_>> {
+        let mut terms: Vec<String> = SearchIndex::tokenize(query).collect();
+        terms.sort();
+        terms.dedup();
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+        for term in &terms {
+            if let Some(term_postings) = self.postings.get(term) {
+                let idf = (1.0 + self.documents.len() as f64 / term_postings.len() as f64).ln();
+                for (index, frequency) in term_postings {
+                    let document = &self.documents[*index];
+                    let mut score = *frequency as f64 / (document.length.max(1) as f64).sqrt() * idf;
+                    if SearchIndex::tokenize(&document.title).any(|title_term| &title_term == term) {
+                        score *= SearchIndex::TITLE_WEIGHT;
+                    }
+                    *scores.entry(*index).or_insert(0.0) += score;
+                }
+            }
+        }
+        let mut hits: Vec<SearchHit> = scores.into_iter()
+            .map(|(index, score)| (&self.documents[index], score))
+            .filter(|(document, _)| course_alias.map(|alias| alias == document.course_alias).unwrap_or(true))
+            .map(|(document, score)| SearchHit {
+                document,
+                score,
+                snippet: String::new(),
+            })
+            .collect();
+        hits.sort_by(|h1, h2| h2.score.partial_cmp(&h1.score).unwrap_or(std::cmp::Ordering::Equal));
+        hits.truncate(limit);
+        for hit in &mut hits {
+            hit.snippet = SearchIndex::snippet(&self.read_text(hit.document), &terms);
+        }
+        hits
+    }
+
+    // The words around the first occurrence of a query term
+    fn snippet(text: &str, terms: &[String]) -> String {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let position = words.iter().position(|word| SearchIndex::tokenize(word).any(|token| terms.contains(&token)));
+        match position {
+            Some(position) => {
+                let start = position.saturating_sub(SearchIndex::SNIPPET_WORDS_BEFORE);
+                let end = (position + SearchIndex::SNIPPET_WORDS_AFTER).min(words.len());
+                format!("{}{}{}",
+                    if start > 0 { "..." } else { "" },
+                    words[start..end].join(" "),
+                    if end < words.len() { "..." } else { "" },
+                )
+            },
+            None => String::new(),
+        }
+    }
+}
+
+impl<'a> SearchHit<'a> {
+    pub fn view(&self, rank: usize) {
+        println!("{}. [{}] {} ({:.2})", rank, self.document.course_alias, self.document.title, self.score);
+        if let Some(path) = &self.document.path {
+            println!("   {}", path.display());
+        }
+        if !self.snippet.is_empty() {
+            println!("   {}", self.snippet);
+        }
+        println!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An index in its own temporary dir, since the texts for snippets are kept on disk
+    fn test_index(name: &str, documents: &[(&str, &str, &str)]) -> SearchIndex {
+        let dir = std::env::temp_dir().join(format!("bbcm-search-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut index = SearchIndex::load(&dir.join("search_index.json")).unwrap();
+        for (key, title, text) in documents {
+            let document = index.index_text(SearchDocument {
+                key: key.to_string(),
+                course_alias: String::from(if key.starts_with("b") { "b" } else { "a" }),
+                kind: String::from("attachment"),
+                title: title.to_string(),
+                path: None,
+                stamp: String::new(),
+                length: 0,
+                terms: Vec::new(),
+            }, text).unwrap();
+            index.documents.push(document);
+        }
+        index.rebuild_postings();
+        index
+    }
+
+    fn words(text: &str) -> Vec<String> {
+        text.split(' ').map(str::to_string).collect()
+    }
+
+    #[test]
+    fn tokenize_lowercases_and_skips_short_words() {
+        let tokens: Vec<String> = SearchIndex::tokenize("Exam in TMA4100, a 2-hour Øving!").collect();
+        assert_eq!(tokens, vec!["exam", "in", "tma4100", "hour", "øving"]);
+        assert_eq!(SearchIndex::tokenize(" - . a ").count(), 0);
+    }
+
+    #[test]
+    fn ranks_by_frequency_and_title() {
+        let index = test_index("rank", &[
+            ("a1", "Notes", "integral once among many other words in a long text about calculus"),
+            ("a2", "Notes", "integral integral integral"),
+            ("a3", "Integral", "nothing else here"),
+            ("a4", "Notes", "derivative"),
+        ]);
+        let keys: Vec<&str> = index.search("Integral", None, 10).iter().map(|hit| hit.document.key.as_str()).collect();
+        assert_eq!(keys, vec!["a2", "a3", "a1"]);
+        assert_eq!(index.search("integral", None, 1).len(), 1);
+        assert!(index.search("missing", None, 10).is_empty());
+    }
+
+    #[test]
+    fn filters_by_course() {
+        let index = test_index("course", &[
+            ("a1", "Notes", "integral"),
+            ("b1", "Notes", "integral"),
+        ]);
+        let hits = index.search("integral", Some("b"), 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].document.key, "b1");
+    }
+
+    #[test]
+    fn snippet_around_first_match() {
+        let text = (1..=40).map(|i| if i == 15 { String::from("Integral,") } else { format!("w{}", i) }).collect::<Vec<String>>().join(" ");
+        let snippet = SearchIndex::snippet(&text, &words("integral"));
+        assert!(snippet.starts_with("...w5 "));
+        assert!(snippet.contains("Integral,"));
+        assert!(snippet.ends_with(" w34..."));
+        assert_eq!(SearchIndex::snippet("short integral text", &words("integral")), "short integral text");
+        assert_eq!(SearchIndex::snippet("no match here", &words("integral")), "");
+    }
+
+    #[test]
+    fn snippets_are_read_from_stored_text() {
+        let index = test_index("snippet", &[("a1", "Notes", "The integral of x")]);
+        let hits = index.search("integral", None, 10);
+        assert_eq!(hits[0].snippet, "The integral of x");
+    }
+}
@@ -0,0 +1,43 @@
+use std::path::Path;
+
+const VIEW_WIDTH: usize = 100;
+// Larger files are most likely not text worth searching, e.g. scanned books
+const MAX_FILE_SIZE: u64 = 50_000_000;
+const TEXT_EXTENSIONS: [&str; 16] = ["txt", "md", "markdown", "csv", "tex", "py", "java", "c", "h", "cpp", "rs", "m", "r", "sql", "json", "xml"];
+
+// Searchable text of a downloaded file, or None for files that aren't indexed
+pub fn extract_text(path: &Path) -> Option<String> {
+    if std::fs::metadata(path).ok()?.len() > MAX_FILE_SIZE {
+        return None;
+    }
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "pdf" => extract_pdf_text(path),
+        "html" | "htm" => Some(html_to_text(&String::from_utf8_lossy(&std::fs::read(path).ok()?))),
+        extension if TEXT_EXTENSIONS.contains(&extension) => Some(String::from_utf8_lossy(&std::fs::read(path).ok()?).to_string()),
+        _ => None,
+    }
+}
+
+pub fn html_to_text(html: &str) -> String {
+    html2text::from_read(html.as_bytes(), VIEW_WIDTH)
+}
+
+// The PDF extractor panics on some fonts and encodings. Those files are skipped, without printing the panic.
+fn extract_pdf_text(path: &Path) -> Option<String> {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let result = std::panic::catch_unwind(|| pdf_extract::extract_text(path));
+    std::panic::set_hook(default_hook);
+    match result {
+        Ok(Ok(text)) => Some(text),
+        Ok(Err(err)) => {
+            eprintln!("Note: Could not extract text from {}: {}", path.display(), err);
+            None
+        },
+        Err(_) => {
+            eprintln!("Note: Could not extract text from {}", path.display());
+            None
+        },
+    }
+}
@@ -0,0 +1,218 @@
+pub mod pages;
+
+use std::path::{Component, Path, PathBuf};
+use std::collections::HashMap;
+use tiny_http::{Header, Request, Response, ResponseBox, Server};
+use crate::bb_session::BBSession;
+use crate::bb_course::BBCourse;
+use crate::bb_course::bb_announcement::bb_user::BBUserCache;
+use crate::bb_course::bb_manifest::{BBManifest, BBManifestContent};
+use crate::search::search_index::SearchIndex;
+use crate::utils::markup_utils::escape_xml;
+use crate::utils::time_utils::{partial_cmp_dt, utc_offset_from_now};
+use crate::utils::url_utils::{percent_decode, query_parameter};
+use chrono::Duration;
+
+// Local web UI over the synced trees and cached course data
+pub struct CourseServer<'a> {
+    pub session: &'a BBSession,
+    pub courses: &'a HashMap<String, BBCourse>,
+    pub users: &'a BBUserCache,
+    pub work_dir: &'a Path,
+    pub calendar_path: PathBuf,
+    pub index: SearchIndex,
+}
+
+impl<'a> CourseServer<'a> {
+    const SEARCH_LIMIT: usize = 25;
+
+    // Serves requests one at a time until the process is stopped
+    pub fn run(&self, address: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let server = Server::http(address).map_err(|err| err.to_string())?;
+        println!("Serving courses on http://{}/", address);
+        for request in server.incoming_requests() {
+            let response = self.route(&request);
+            if let Err(err) = request.respond(response) {
+                eprintln!("Error sending response: {}", err);
+            }
+        }
+        Ok(())
+    }
+
+    fn route(&self, request: &Request) -> ResponseBox {
+        let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+        let segments: Vec<String> = path.split('/').filter(|segment| !segment.is_empty()).map(percent_decode).collect();
+        let segments: Vec<&str> = segments.iter().map(|segment| segment.as_str()).collect();
+        match segments.as_slice() {
+            [] => self.html("Courses", &pages::course_list(&self.sorted_courses())),
+            ["courses", alias] => self.with_course(alias, |course| self.course_page(course, None)),
+            ["courses", alias, "content", content_id] => self.with_course(alias, |course| self.course_page(course, Some(content_id))),
+            ["courses", alias, "announcements"] => self.with_course(alias, |course| self.announcements_page(course)),
+            ["files", alias, file_path @ ..] => self.with_course(alias, |course| self.file(course, file_path)),
+            ["deadlines"] => self.deadlines_page(),
+            ["search"] => self.search_page(&query_parameter(query, "q").unwrap_or_default()),
+            ["calendar.ics"] if self.calendar_path.exists() => self.file_response(&self.calendar_path),
+            _ => self.not_found(),
+        }
+    }
+
+    fn sorted_courses(&self) -> Vec<&BBCourse> {
+        let mut courses: Vec<&BBCourse> = self.courses.values().collect();
+        courses.sort_by(|c1, c2| c1.alias.cmp(&c2.alias));
+        courses
+    }
+
+    fn with_course(&self, alias: &str, page: impl FnOnce(&BBCourse) -> ResponseBox) -> ResponseBox {
+        match self.courses.get(alias) {
+            Some(course) => page(course),
+            None => self.not_found(),
+        }
+    }
+
+    // The course root, or a content folder. Files that don't belong to synced content, like submissions, are listed on the root page.
+    fn course_page(&self, course: &BBCourse, content_id: Option<&str>) -> ResponseBox {
+        let manifest = match course.load_manifest() {
+            Ok(manifest) => manifest,
+            Err(err) => return self.error(&err.to_string()),
+        };
+        let content = match content_id.map(|id| manifest.contents.get(id)) {
+            Some(None) => return self.not_found(),
+            Some(content) => content,
+            None => None,
+        };
+        let mut children: Vec<&BBManifestContent> = manifest.contents.values().filter(|child| match content {
+            Some(content) => child.parent_id == content.id,
+            None => !manifest.contents.contains_key(&child.parent_id),
+        }).collect();
+        children.sort_by(|c1, c2| c1.title.cmp(&c2.title));
+        let mut body = pages::breadcrumbs(course, &manifest, content);
+        body.push_str(&pages::content_list(course, &manifest, &children));
+        if content.is_none() {
+            body.push_str(&CourseServer::other_files(course, &manifest));
+        }
+        let title = content.map(|content| content.title.clone()).unwrap_or_else(|| course.alias.clone());
+        self.html(&title, &body)
+    }
+
+    fn other_files(course: &BBCourse, manifest: &BBManifest) -> String {
+        let mut top_dirs: Vec<PathBuf> = manifest.files.values()
+            .filter(|file| !manifest.contents.contains_key(&file.content_id))
+            .filter_map(|file| file.path.components().next().map(|component| PathBuf::from(component.as_os_str())))
+            .collect();
+        top_dirs.sort();
+        top_dirs.dedup();
+        if top_dirs.is_empty() {
+            return String::new();
+        }
+        let links: Vec<String> = top_dirs.iter().map(|dir| {
+            format!("<li><a href=\"{}/\">{}/</a></li>", pages::file_url(&course.alias, dir), escape_xml(&dir.to_string_lossy()))
+        }).collect();
+        format!("<h2>Other files</h2><ul>{}</ul>", links.join(""))
+    }
+
+    fn announcements_page(&self, course: &BBCourse) -> ResponseBox {
+        match course.load_announcement_cache(self.work_dir) {
+            Ok(cache) => {
+                let mut announcements = cache.announcements();
+                announcements.reverse();
+                self.html(&format!("Announcements for {}", course.alias), &pages::announcement_list(&announcements, self.users))
+            },
+            Err(err) => self.error(&err.to_string()),
+        }
+    }
+
+    // From the gradebooks cached by bbcm due, bbcm gradebooks and bbcm watch, shown like bbcm due. Blackboard isn't contacted.
+    fn deadlines_page(&self) -> ResponseBox {
+        let mut session = self.session.clone();
+        session.offline = true;
+        let overdue_since = utc_offset_from_now(-Duration::days(crate::OVERDUE_DAYS));
+        let mut deadlines = Vec::new();
+        let mut errors = String::new();
+        for course in self.sorted_courses() {
+            match course.get_course_gradebook(&session) {
+                Ok(gradebook_columns) => {
+                    for gbc in gradebook_columns {
+                        if let Some(urgency) = gbc.urgency().filter(|_| partial_cmp_dt(&gbc.due, &overdue_since) == Some(std::cmp::Ordering::Greater)) {
+                            deadlines.push((urgency, course.alias.as_str(), gbc));
+                        }
+                    }
+                },
+                Err(_) => errors.push_str(&format!("<p class=\"meta\">No cached gradebook for {}, run <code>bbcm due</code> to fetch it.</p>", escape_xml(&course.alias))),
+            }
+        }
+        deadlines.sort_by(|(u1, _, gbc1), (u2, _, gbc2)| {
+            u1.cmp(u2).then(partial_cmp_dt(&gbc1.due, &gbc2.due).unwrap_or(std::cmp::Ordering::Equal))
+        });
+        self.html("Deadlines", &format!("{}{}", errors, pages::deadline_list(&deadlines)))
+    }
+
+    fn search_page(&self, query: &str) -> ResponseBox {
+        let hits = if query.is_empty() { Vec::new() } else { self.index.search(query, None, CourseServer::SEARCH_LIMIT) };
+        let course_dirs: Vec<(&str, &Path)> = self.courses.values().map(|course| (course.alias.as_str(), course.out_dir())).collect();
+        self.html("Search", &pages::search_results(query, &hits, &course_dirs))
+    }
+
+    // Paths are resolved below the course folder only
+    fn file(&self, course: &BBCourse, file_path: &[&str]) -> ResponseBox {
+        let relative_path: PathBuf = file_path.iter().collect();
+        if relative_path.components().any(|component| !matches!(component, Component::Normal(_))) {
+            return self.not_found();
+        }
+        let path = course.out_dir().join(&relative_path);
+        if path.is_dir() {
+            self.html(&relative_path.to_string_lossy(), &pages::directory_listing(&course.alias, &relative_path, &path))
+        } else if path.is_file() {
+            self.file_response(&path)
+        } else {
+            self.not_found()
+        }
+    }
+
+    fn file_response(&self, path: &Path) -> ResponseBox {
+        match std::fs::File::open(path) {
+            Ok(file) => Response::from_file(file).with_header(CourseServer::content_type(CourseServer::mimetype(path))).boxed(),
+            Err(err) => self.error(&err.to_string()),
+        }
+    }
+
+    fn mimetype(path: &Path) -> &'static str {
+        match path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_lowercase()).as_deref() {
+            Some("pdf") => "application/pdf",
+            Some("html") | Some("htm") => "text/html; charset=utf-8",
+            Some("txt") | Some("md") | Some("url") | Some("csv") => "text/plain; charset=utf-8",
+            Some("ics") => "text/calendar; charset=utf-8",
+            Some("png") => "image/png",
+            Some("jpg") | Some("jpeg") => "image/jpeg",
+            Some("gif") => "image/gif",
+            Some("svg") => "image/svg+xml",
+            Some("mp4") => "video/mp4",
+            Some("mp3") => "audio/mpeg",
+            Some("zip") => "application/zip",
+            _ => "application/octet-stream",
+        }
+    }
+
+    fn content_type(mimetype: &str) -> Header {
+        Header::from_bytes("Content-Type", mimetype).expect("Invalid Content-Type header")
+    }
+
+    fn html(&self, title: &str, body: &str) -> ResponseBox {
+        Response::from_string(pages::layout(title, body, self.calendar_path.exists()))
+            .with_header(CourseServer::content_type("text/html; charset=utf-8"))
+            .boxed()
+    }
+
+    fn not_found(&self) -> ResponseBox {
+        Response::from_string(pages::layout("Not found", "<p>This page doesn't exist.</p>", self.calendar_path.exists()))
+            .with_header(CourseServer::content_type("text/html; charset=utf-8"))
+            .with_status_code(404)
+            .boxed()
+    }
+
+    fn error(&self, message: &str) -> ResponseBox {
+        Response::from_string(pages::layout("Error", &format!("<p>{}</p>", escape_xml(message)), self.calendar_path.exists()))
+            .with_header(CourseServer::content_type("text/html; charset=utf-8"))
+            .with_status_code(500)
+            .boxed()
+    }
+}
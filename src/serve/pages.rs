@@ -0,0 +1,173 @@
+use std::path::Path;
+use crate::bb_course::BBCourse;
+use crate::bb_course::bb_announcement::BBAnnouncement;
+use crate::bb_course::bb_announcement::bb_user::BBUserCache;
+use crate::bb_course::bb_content::bb_content_classes;
+use crate::bb_course::bb_gradebook::{BBGradebookColumn, Urgency};
+use crate::bb_course::bb_manifest::{BBManifest, BBManifestContent};
+use crate::search::search_index::SearchHit;
+use crate::utils::markup_utils::{escape_xml, sanitize_html};
+use crate::utils::time_utils::{local_rfc2822, relative_to_now};
+use crate::utils::url_utils::percent_encode_path;
+
+const STYLE: &str = "body{font-family:sans-serif;max-width:60em;margin:auto;padding:1em;line-height:1.4}\
+nav a{margin-right:1em}li{margin:.2em 0}.meta{color:#666;font-size:.9em}\
+article{border-top:1px solid #ccc;padding:.5em 0}.snippet{color:#333}";
+
+// Every page shares the navigation bar and search form
+pub fn layout(title: &str, body: &str, has_calendar: bool) -> String {
+    format!("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title} - bbcm</title><style>{style}</style></head><body>\
+<nav><a href=\"/\">Courses</a><a href=\"/deadlines\">Deadlines</a>{calendar}\
+<form action=\"/search\" style=\"display:inline\"><input name=\"q\" placeholder=\"Search\"></form></nav>\
+<h1>{title}</h1>{body}</body></html>",
+        title = escape_xml(title),
+        style = STYLE,
+        calendar = if has_calendar { "<a href=\"/calendar.ics\">Calendar</a>" } else { "" },
+        body = body,
+    )
+}
+
+pub fn file_url(course_alias: &str, relative_path: &Path) -> String {
+    format!("/files/{}/{}", percent_encode_path(course_alias), percent_encode_path(&relative_path.to_string_lossy()))
+}
+
+pub fn course_list(courses: &[&BBCourse]) -> String {
+    let mut body = String::from("<ul>");
+    for course in courses {
+        body.push_str(&format!("<li><a href=\"/courses/{alias}\">{alias}</a>: {code} {semester}{name} <span class=\"meta\">(<a href=\"/courses/{alias}/announcements\">announcements</a>)</span></li>",
+            alias = escape_xml(&course.alias),
+            code = escape_xml(&course.course_code),
+            semester = escape_xml(&course.semester),
+            name = if course.info.name.is_empty() { String::new() } else { format!(" - {}", escape_xml(&course.info.name)) },
+        ));
+    }
+    body.push_str("</ul>");
+    if courses.is_empty() {
+        body = String::from("<p>No courses registered yet.</p>");
+    }
+    body
+}
+
+// Parents of a content item up to the course root, outermost first
+pub fn breadcrumbs(course: &BBCourse, manifest: &BBManifest, content: Option<&BBManifestContent>) -> String {
+    let mut crumbs = Vec::new();
+    let mut current = content;
+    while let Some(content) = current {
+        crumbs.push(format!("<a href=\"/courses/{}/content/{}\">{}</a>", escape_xml(&course.alias), escape_xml(&content.id), escape_xml(&content.title)));
+        current = manifest.contents.get(&content.parent_id);
+    }
+    crumbs.push(format!("<a href=\"/courses/{0}\">{0}</a>", escape_xml(&course.alias)));
+    crumbs.reverse();
+    format!("<p class=\"meta\">{}</p>", crumbs.join(" / "))
+}
+
+// Folders link to their own page, other content items are listed with their files
+pub fn content_list(course: &BBCourse, manifest: &BBManifest, children: &[&BBManifestContent]) -> String {
+    let mut body = String::from("<ul>");
+    for child in children {
+        if child.content_handler == bb_content_classes::FOLDER {
+            body.push_str(&format!("<li>&#128193; <a href=\"/courses/{}/content/{}\">{}</a></li>", escape_xml(&course.alias), escape_xml(&child.id), escape_xml(&child.title)));
+        } else {
+            body.push_str(&format!("<li>{}<ul>", escape_xml(&child.title)));
+            for file in manifest.files.values().filter(|file| file.content_id == child.id) {
+                body.push_str(&format!("<li><a href=\"{}\">{}</a> <span class=\"meta\">{}</span></li>",
+                    file_url(&course.alias, &file.path),
+                    escape_xml(&file.path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default()),
+                    escape_xml(&file.mimetype),
+                ));
+            }
+            body.push_str("</ul></li>");
+        }
+    }
+    body.push_str("</ul>");
+    if children.is_empty() {
+        body = String::from("<p>No content synced here yet.</p>");
+    }
+    body
+}
+
+pub fn announcement_list(announcements: &[&BBAnnouncement], users: &BBUserCache) -> String {
+    let mut body = String::new();
+    for announcement in announcements {
+        body.push_str(&format!("<article><h2>{}</h2><p class=\"meta\">{} - {}</p>{}</article>",
+            escape_xml(&announcement.title),
+            escape_xml(&users.display_name(&announcement.creator)),
+            local_rfc2822(&announcement.created),
+            sanitize_html(&announcement.body),
+        ));
+    }
+    if announcements.is_empty() {
+        body = String::from("<p>No announcements cached yet.</p>");
+    }
+    body
+}
+
+pub fn deadline_list(deadlines: &[(Urgency, &str, BBGradebookColumn)]) -> String {
+    let mut body = String::new();
+    let mut current_urgency = None;
+    for (urgency, alias, gbc) in deadlines {
+        if current_urgency != Some(*urgency) {
+            if current_urgency.is_some() {
+                body.push_str("</ul>");
+            }
+            body.push_str(&format!("<h2>{}</h2><ul>", urgency.heading()));
+            current_urgency = Some(*urgency);
+        }
+        body.push_str(&format!("<li>[<a href=\"/courses/{alias}\">{alias}</a>] {} <span class=\"meta\">due {} ({})</span></li>",
+            escape_xml(&gbc.name),
+            local_rfc2822(&gbc.due),
+            relative_to_now(&gbc.due),
+            alias = escape_xml(alias),
+        ));
+    }
+    if current_urgency.is_some() {
+        body.push_str("</ul>");
+    } else {
+        body.push_str("<p>No deadlines found.</p>");
+    }
+    body
+}
+
+pub fn search_results(query: &str, hits: &[SearchHit], course_dirs: &[(&str, &Path)]) -> String {
+    let mut body = format!("<form action=\"/search\"><input name=\"q\" value=\"{}\" size=\"40\"> <button>Search</button></form>", escape_xml(query));
+    for hit in hits {
+        let document = hit.document;
+        // Files link to the local copy, announcements to the course announcements
+        let link = match (&document.path, course_dirs.iter().find(|(alias, _)| *alias == document.course_alias)) {
+            (Some(path), Some((alias, out_dir))) => path.strip_prefix(out_dir).ok().map(|relative_path| file_url(alias, relative_path)),
+            (None, Some((alias, _))) => Some(format!("/courses/{}/announcements", percent_encode_path(alias))),
+            _ => None,
+        };
+        body.push_str(&format!("<article><h3>{}</h3><p class=\"meta\">{} - {}</p><p class=\"snippet\">{}</p></article>",
+            match link {
+                Some(link) => format!("<a href=\"{}\">{}</a>", link, escape_xml(&document.title)),
+                None => escape_xml(&document.title),
+            },
+            escape_xml(&document.course_alias),
+            escape_xml(&document.kind),
+            escape_xml(&hit.snippet),
+        ));
+    }
+    if hits.is_empty() && !query.is_empty() {
+        body.push_str("<p>No results found.</p>");
+    }
+    body
+}
+
+pub fn directory_listing(course_alias: &str, relative_dir: &Path, dir: &Path) -> String {
+    let mut entries: Vec<(String, bool)> = std::fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|entry| entry.ok()).map(|entry| (entry.file_name().to_string_lossy().to_string(), entry.path().is_dir())).collect())
+        .unwrap_or_default();
+    entries.sort();
+    let mut body = String::from("<ul>");
+    for (name, is_dir) in entries.iter().filter(|(name, _)| !name.starts_with('.')) {
+        body.push_str(&format!("<li><a href=\"{}{}\">{}{}</a></li>",
+            file_url(course_alias, &relative_dir.join(name)),
+            if *is_dir { "/" } else { "" },
+            escape_xml(name),
+            if *is_dir { "/" } else { "" },
+        ));
+    }
+    body.push_str("</ul>");
+    body
+}
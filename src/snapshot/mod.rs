@@ -0,0 +1,272 @@
+use std::path::{Path, PathBuf};
+use std::io::Read;
+use std::collections::{HashMap, HashSet};
+use git2::{Delta, IndexEntry, IndexTime, Oid, Repository, Signature};
+use crate::bb_course::BBCourse;
+use crate::bb_course::bb_announcement::bb_user::BBUserCache;
+use crate::search::text_extraction::html_to_text;
+use crate::utils::filename_utils::valid_filename;
+use crate::utils::fs_utils::file_sha256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LargeFileRule {
+    Skip,
+    Pointer,
+}
+
+impl std::str::FromStr for LargeFileRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LargeFileRule, String> {
+        match s {
+            "skip" => Ok(LargeFileRule::Skip),
+            "pointer" => Ok(LargeFileRule::Pointer),
+            _ => Err(format!("Unknown large file rule \"{}\" (expected skip or pointer)", s)),
+        }
+    }
+}
+
+pub struct SnapshotConfig {
+    pub large_files: LargeFileRule,
+    pub large_file_size: u64,
+}
+
+impl SnapshotConfig {
+    const DEFAULT_LARGE_FILE_MB: u64 = 50;
+
+    // Snapshots are enabled by BBCM_GIT_SNAPSHOTS, and large binaries are configured by BBCM_GIT_LARGE_FILES and BBCM_GIT_LARGE_FILE_MB
+    pub fn from_env() -> Result<Option<SnapshotConfig>, String> {
+        match std::env::var("BBCM_GIT_SNAPSHOTS").unwrap_or_default().as_str() {
+            "" | "0" | "false" | "no" => return Ok(None),
+            _ => {},
+        }
+        let large_files = match std::env::var("BBCM_GIT_LARGE_FILES") {
+            Ok(val) => val.parse()?,
+            Err(_) => LargeFileRule::Pointer,
+        };
+        let large_file_mb = match std::env::var("BBCM_GIT_LARGE_FILE_MB") {
+            Ok(val) => val.parse::<u64>().map_err(|_| format!("Invalid BBCM_GIT_LARGE_FILE_MB \"{}\"", val))?,
+            Err(_) => SnapshotConfig::DEFAULT_LARGE_FILE_MB,
+        };
+        Ok(Some(SnapshotConfig {
+            large_files,
+            large_file_size: large_file_mb*1000000,
+        }))
+    }
+}
+
+// Bookkeeping and partial downloads of bbcm, which are never part of a snapshot
+const EXCLUDED_NAMES: &[&str] = &[".git", ".bbcm_manifest.json", ".bbcm_course.json", ".bbcm-history"];
+const EXCLUDED_SUFFIXES: &[&str] = &[".part", ".bbcm-previous", ".bbcm-link", ".bbcm-restore", ".bbcm-unzip"];
+// Written by bbcm for snapshots only, so that it can't collide with a content folder from Blackboard.
+// Unlike the names above it is part of the snapshot.
+const ANNOUNCEMENTS_DIR: &str = ".bbcm-announcements";
+
+fn is_excluded(name: &str) -> bool {
+    EXCLUDED_NAMES.contains(&name) || EXCLUDED_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+}
+
+// Binary like git sees it: a NUL byte early in the file
+fn is_binary(path: &Path) -> bool {
+    let mut start = Vec::new();
+    std::fs::File::open(path).and_then(|file| file.take(8000).read_to_end(&mut start)).map(|_| start.contains(&0)).unwrap_or(false)
+}
+
+fn files_below(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if is_excluded(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            files_below(root, &path, files)?;
+        } else if path.is_file() {
+            files.push(path.strip_prefix(root).unwrap().to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+// One Markdown file per cached announcement, so that snapshots show them being added, edited and removed.
+// Only files that were part of the last snapshot are removed.
+fn write_announcements(repo: &Repository, course: &BBCourse, work_dir: &Path, users: &BBUserCache) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = course.out_dir().join(ANNOUNCEMENTS_DIR);
+    let cache = course.load_announcement_cache(work_dir)?;
+    let mut written = Vec::new();
+    for announcement in cache.announcements() {
+        let mut filename = valid_filename(&format!("{} {}.md", announcement.created.get(..10).unwrap_or_default(), announcement.title));
+        if written.contains(&filename) {
+            filename = valid_filename(&format!("{} {} ({}).md", announcement.created.get(..10).unwrap_or_default(), announcement.title, announcement.id));
+        }
+        std::fs::create_dir_all(&dir)?;
+        let markdown = format!("# {}\n\n{} - {}\n\n{}",
+            announcement.title,
+            users.display_name(&announcement.creator),
+            announcement.created,
+            html_to_text(&announcement.body),
+        );
+        // Unchanged files are left alone, so that their index entries can be reused
+        if std::fs::read_to_string(dir.join(&filename)).ok().as_ref() != Some(&markdown) {
+            std::fs::write(dir.join(&filename), markdown)?;
+        }
+        written.push(filename);
+    }
+    let previous_tree = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+    let previous_dir = previous_tree.as_ref().and_then(|tree| tree.get_name(ANNOUNCEMENTS_DIR)).and_then(|entry| entry.to_object(repo).ok()?.into_tree().ok());
+    if let Some(previous_dir) = previous_dir {
+        for entry in previous_dir.iter() {
+            if let Some(name) = entry.name().filter(|name| !written.iter().any(|written| written == name)) {
+                let _ = std::fs::remove_file(dir.join(name));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Index entries carry the stat data of the file, so that git status sees the working tree as unchanged,
+// also for files that are tracked by a pointer
+fn index_entry(path: &Path, relative_path: &Path, id: Oid) -> Result<IndexEntry, Box<dyn std::error::Error>> {
+    let metadata = std::fs::metadata(path)?;
+    let mut entry = IndexEntry {
+        ctime: IndexTime::new(0, 0),
+        mtime: IndexTime::new(0, 0),
+        dev: 0,
+        ino: 0,
+        mode: 0o100644,
+        uid: 0,
+        gid: 0,
+        file_size: metadata.len() as u32,
+        id,
+        flags: 0,
+        flags_extended: 0,
+        path: relative_path.to_string_lossy().replace('\\', "/").into_bytes(),
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        entry.ctime = IndexTime::new(metadata.ctime() as i32, metadata.ctime_nsec() as u32);
+        entry.mtime = IndexTime::new(metadata.mtime() as i32, metadata.mtime_nsec() as u32);
+        entry.dev = metadata.dev() as u32;
+        entry.ino = metadata.ino() as u32;
+        entry.uid = metadata.uid();
+        entry.gid = metadata.gid();
+    }
+    Ok(entry)
+}
+
+// Whether the file is unchanged since the entry was added, going by the stat data git itself compares
+fn same_stat(previous: &IndexEntry, entry: &IndexEntry) -> bool {
+    cfg!(unix)
+        && previous.mtime == entry.mtime
+        && previous.ctime == entry.ctime
+        && previous.file_size == entry.file_size
+        && previous.ino == entry.ino
+        && previous.dev == entry.dev
+}
+
+// In the format of Git LFS, so that git-lfs can smudge and clean the file if it is installed
+fn pointer(path: &Path, size: u64) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(format!("version https://git-lfs.github.com/spec/v1\noid sha256:{}\nsize {}\n", file_sha256(path)?, size))
+}
+
+// Counts of changed files and announcements, followed by the changed paths
+fn summary(course: &BBCourse, changes: &[(Delta, PathBuf)]) -> String {
+    let count = |announcements: bool, status: Delta| changes.iter()
+        .filter(|(delta, path)| *delta == status && path.starts_with(ANNOUNCEMENTS_DIR) == announcements)
+        .count();
+    let mut parts = Vec::new();
+    for (announcements, status, label) in &[
+        (false, Delta::Added, "added"),
+        (false, Delta::Modified, "updated"),
+        (false, Delta::Deleted, "removed"),
+        (true, Delta::Added, "new announcements"),
+        (true, Delta::Modified, "edited announcements"),
+        (true, Delta::Deleted, "removed announcements"),
+    ] {
+        let n = count(*announcements, *status);
+        if n > 0 {
+            parts.push(format!("{} {}", n, label));
+        }
+    }
+    let mut message = format!("Sync {}: {}\n", course.alias, parts.join(", "));
+    for (status, heading) in &[(Delta::Added, "Added"), (Delta::Modified, "Updated"), (Delta::Deleted, "Removed")] {
+        let paths: Vec<String> = changes.iter()
+            .filter(|(delta, _)| delta == status)
+            .map(|(_, path)| format!("- {}", path.display()))
+            .collect();
+        if !paths.is_empty() {
+            message.push_str(&format!("\n{}:\n{}\n", heading, paths.join("\n")));
+        }
+    }
+    message
+}
+
+// Commits the course folder to a git repository in it, if anything changed since the last snapshot. Returns the first line of the commit message.
+pub fn commit_course(course: &BBCourse, work_dir: &Path, users: &BBUserCache, config: &SnapshotConfig) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let root = course.out_dir();
+    let repo = Repository::open(root).or_else(|_| Repository::init(root))?;
+    write_announcements(&repo, course, work_dir, users)?;
+
+    let mut files = Vec::new();
+    files_below(root, root, &mut files)?;
+    files.sort();
+
+    // Large binaries are excluded, or marked as LFS files, for plain git as well
+    let mut excluded: Vec<String> = EXCLUDED_NAMES.iter().map(|name| format!("/{}", name))
+        .chain(EXCLUDED_SUFFIXES.iter().map(|suffix| format!("*{}", suffix)))
+        .collect();
+    let mut attributes = Vec::new();
+    let attributes_path = repo.path().join("info").join("attributes");
+    let previous_attributes: HashSet<String> = std::fs::read_to_string(&attributes_path).unwrap_or_default().lines().map(str::to_string).collect();
+    let mut index = repo.index()?;
+    // Files are only read and hashed again when their stat data differs from the last snapshot
+    let previous_entries: HashMap<Vec<u8>, IndexEntry> = index.iter().map(|entry| (entry.path.clone(), entry)).collect();
+    index.clear()?;
+    for relative_path in files {
+        let path = root.join(&relative_path);
+        let size = std::fs::metadata(&path)?.len();
+        let git_path = format!("/{}", relative_path.to_string_lossy().replace('\\', "/"));
+        let attribute = format!("{} filter=lfs diff=lfs merge=lfs -text", git_path.replace(' ', "[[:space:]]"));
+        let is_pointer = size > config.large_file_size && is_binary(&path);
+        if is_pointer {
+            match config.large_files {
+                LargeFileRule::Skip => {
+                    excluded.push(git_path);
+                    continue;
+                },
+                LargeFileRule::Pointer => attributes.push(attribute.clone()),
+            }
+        }
+        let mut entry = index_entry(&path, &relative_path, Oid::zero())?;
+        // The blob of a pointer differs from the file, so it is only reused if the file was a pointer before as well
+        let was_pointer = previous_attributes.contains(&attribute);
+        entry.id = match previous_entries.get(&entry.path).filter(|previous| same_stat(previous, &entry) && was_pointer == is_pointer) {
+            Some(previous) => previous.id,
+            None if is_pointer => repo.blob(pointer(&path, size)?.as_bytes())?,
+            None => repo.blob(&std::fs::read(&path)?)?,
+        };
+        index.add(&entry)?;
+    }
+    std::fs::create_dir_all(repo.path().join("info"))?;
+    std::fs::write(repo.path().join("info").join("exclude"), format!("{}\n", excluded.join("\n")))?;
+    std::fs::write(&attributes_path, format!("{}\n", attributes.join("\n")))?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    index.write()?;
+
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    let parent_tree = parent.as_ref().map(|parent| parent.tree()).transpose()?;
+    if parent_tree.as_ref().map(|parent_tree| parent_tree.id() == tree.id()).unwrap_or(false) {
+        return Ok(None);
+    }
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+    let changes: Vec<(Delta, PathBuf)> = diff.deltas().filter_map(|delta| {
+        let file = if delta.status() == Delta::Deleted { delta.old_file() } else { delta.new_file() };
+        file.path().map(|path| (delta.status(), path.to_path_buf()))
+    }).collect();
+    let message = summary(course, &changes);
+    let signature = repo.signature().or_else(|_| Signature::now("bbcm", "bbcm@localhost"))?;
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    repo.commit(Some("HEAD"), &signature, &signature, &message, &tree, &parents)?;
+    Ok(message.lines().next().map(str::to_string))
+}
@@ -0,0 +1,71 @@
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+// Size of a file, or of all files below a directory
+pub fn path_size(path: &Path) -> u64 {
+    if path.is_dir() {
+        std::fs::read_dir(path)
+            .map(|entries| entries.filter_map(|entry| entry.ok()).map(|entry| path_size(&entry.path())).sum())
+            .unwrap_or(0)
+    } else {
+        std::fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0)
+    }
+}
+
+// Hex encoded SHA-256 of a file's content
+pub fn file_sha256(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+// SHA-256 of a file, or of the relative paths and contents of all files below a directory
+pub fn path_sha256(path: &Path) -> std::io::Result<String> {
+    if path.is_dir() {
+        let mut entries = Vec::new();
+        collect_file_hashes(path, path, &mut entries)?;
+        entries.sort();
+        let mut hasher = Sha256::new();
+        for (relative_path, hash) in entries {
+            hasher.update(format!("{}\0{}\n", relative_path, hash).as_bytes());
+        }
+        Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+    } else {
+        file_sha256(path)
+    }
+}
+
+fn collect_file_hashes(root: &Path, path: &Path, entries: &mut Vec<(String, String)>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(path)? {
+        let entry_path = entry?.path();
+        if entry_path.is_dir() {
+            collect_file_hashes(root, &entry_path, entries)?;
+        } else {
+            let relative_path = entry_path.strip_prefix(root).unwrap_or(&entry_path).to_string_lossy().to_string();
+            entries.push((relative_path, file_sha256(&entry_path)?));
+        }
+    }
+    Ok(())
+}
+
+pub fn remove_path(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+// Copies a file, or a directory with everything below it
+pub fn copy_path(from: &Path, to: &Path) -> std::io::Result<()> {
+    if from.is_dir() {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        std::fs::copy(from, to)?;
+    }
+    Ok(())
+}
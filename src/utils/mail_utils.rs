@@ -0,0 +1,46 @@
+// Header values with non-ASCII characters are written as RFC 2047 encoded words.
+pub fn encode_header_value(s: &str) -> String {
+    if s.is_ascii() {
+        return s.to_string();
+    }
+    // Keep every encoded word within the 75 character limit, without splitting characters
+    let mut words = Vec::new();
+    let mut chunk = String::new();
+    for c in s.chars() {
+        if chunk.len() + c.len_utf8() > 45 {
+            words.push(format!("=?UTF-8?B?{}?=", base64::encode(&chunk)));
+            chunk.clear();
+        }
+        chunk.push(c);
+    }
+    if !chunk.is_empty() {
+        words.push(format!("=?UTF-8?B?{}?=", base64::encode(&chunk)));
+    }
+    words.join("\n ")
+}
+
+pub fn quoted_display_name(name: &str) -> String {
+    if name.is_ascii() {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        encode_header_value(name)
+    }
+}
+
+// Base64 body, wrapped at 76 characters as required for MIME
+pub fn base64_body(data: &[u8]) -> String {
+    let encoded = base64::encode(data);
+    let mut wrapped = String::with_capacity(encoded.len() + encoded.len() / 76 + 1);
+    for (i, c) in encoded.chars().enumerate() {
+        if i > 0 && i % 76 == 0 {
+            wrapped.push('\n');
+        }
+        wrapped.push(c);
+    }
+    wrapped
+}
+
+// Characters allowed in the local part of a Message-ID
+pub fn message_id_part(s: &str) -> String {
+    s.chars().map(|c| if c.is_ascii_alphanumeric() || "._-".contains(c) { c } else { '_' }).collect()
+}
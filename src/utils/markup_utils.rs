@@ -0,0 +1,115 @@
+use scraper::{Html, Node, ElementRef};
+
+const ALLOWED_TAGS: [&str; 31] = [
+    "a", "b", "blockquote", "br", "code", "div", "em", "h1", "h2", "h3", "h4", "h5", "h6", "hr", "i", "img",
+    "li", "ol", "p", "pre", "s", "span", "strong", "sub", "sup", "table", "tbody", "td", "th", "tr", "ul",
+];
+
+const ALLOWED_ATTRIBUTES: [&str; 5] = [
+    "href",
+    "src",
+    "alt",
+    "title",
+    "colspan",
+];
+
+const ALLOWED_SCHEMES: [&str; 3] = [
+    "http",
+    "https",
+    "mailto",
+];
+
+// Elements that are dropped together with everything inside them
+const DROPPED_TAGS: [&str; 6] = [
+    "script",
+    "style",
+    "iframe",
+    "object",
+    "embed",
+    "form",
+];
+
+const VOID_TAGS: [&str; 3] = [
+    "br",
+    "hr",
+    "img",
+];
+
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// Keeps a small set of formatting tags and attributes. Unknown tags are unwrapped, and scripting is removed.
+pub fn sanitize_html(html: &str) -> String {
+    let fragment = Html::parse_fragment(html);
+    let mut out = String::new();
+    sanitize_children(fragment.root_element(), &mut out);
+    out
+}
+
+fn sanitize_children(element: ElementRef, out: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => out.push_str(&escape_xml(text)),
+            Node::Element(_) => sanitize_element(ElementRef::wrap(child).unwrap(), out),
+            _ => (),
+        }
+    }
+}
+
+// Web and mail links, and relative URLs. Browsers ignore ASCII whitespace and control characters in the scheme, so they are removed first.
+fn is_safe_url(url: &str) -> bool {
+    let url: String = url.chars().filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_control()).collect();
+    match url.find([':', '/', '?', '#']) {
+        Some(i) if url[i..].starts_with(':') => ALLOWED_SCHEMES.contains(&url[..i].to_lowercase().as_str()),
+        _ => true,
+    }
+}
+
+fn sanitize_element(element: ElementRef, out: &mut String) {
+    let name = element.value().name();
+    if DROPPED_TAGS.contains(&name) {
+        return;
+    }
+    if !ALLOWED_TAGS.contains(&name) {
+        sanitize_children(element, out);
+        return;
+    }
+    out.push('<');
+    out.push_str(name);
+    for (attribute, value) in element.value().attrs() {
+        let is_url = attribute == "href" || attribute == "src";
+        if ALLOWED_ATTRIBUTES.contains(&attribute) && (!is_url || is_safe_url(value)) {
+            out.push_str(&format!(" {}=\"{}\"", attribute, escape_xml(value)));
+        }
+    }
+    out.push('>');
+    if !VOID_TAGS.contains(&name) {
+        sanitize_children(element, out);
+        out.push_str(&format!("</{}>", name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_web_mail_and_relative_urls() {
+        for url in &["https://example.com/a", "HTTP://example.com", "mailto:a@example.com", "notes.pdf", "/a/b?c=d:e", "#top"] {
+            assert!(is_safe_url(url), "{}", url);
+        }
+    }
+
+    #[test]
+    fn removes_other_schemes() {
+        for url in &["javascript:alert(1)", " JavaScript:alert(1)", "java\tscript:alert(1)", "java\nscript:alert(1)", "\u{1}javascript:alert(1)", "data:text/html,x", "vbscript:x", "file:///etc/passwd"] {
+            assert!(!is_safe_url(url), "{}", url);
+        }
+        assert_eq!(sanitize_html("<a href=\"java&#9;script:alert(1)\" title=\"t\">x</a>"), "<a title=\"t\">x</a>");
+    }
+}
@@ -1,4 +1,10 @@
+pub mod content_line_utils;
 pub mod filename_utils;
+pub mod fs_utils;
 pub mod input_utils;
+pub mod mail_utils;
+pub mod markup_utils;
+#[allow(dead_code)]
 pub mod predicate_utils;
-pub mod time_utils;
\ No newline at end of file
+pub mod time_utils;
+pub mod url_utils;
\ No newline at end of file
@@ -1,4 +1,5 @@
 use chrono::prelude::*;
+use chrono::Duration;
 use std::cmp::Ordering::{self, *};
 // WARNING: This function assumes times on format "2021-08-13T07:34:54.795Z"
 pub fn partial_cmp_dt(dt1: &str, dt2: &str) -> Option<Ordering> {
@@ -21,4 +22,92 @@ pub fn utc_now() -> String {
 
 pub fn local_rfc2822(dt: &str) -> String {
     dt.parse::<DateTime<Local>>().map(|dt| dt.to_rfc2822()).unwrap_or_else(|_| String::from("<null>"))
-}
\ No newline at end of file
+}
+
+// Accepts "2021-08-13" (start of day, UTC) or a full RFC 3339 timestamp.
+pub fn utc_from_date_arg(arg: &str) -> Result<String, chrono::ParseError> {
+    if let Ok(date) = NaiveDate::parse_from_str(arg, "%Y-%m-%d") {
+        Ok(format!("{}", Utc.from_utc_datetime(&date.and_hms(0, 0, 0))))
+    } else {
+        arg.parse::<DateTime<Utc>>().map(|dt| format!("{}", dt))
+    }
+}
+
+pub fn utc_rfc3339(dt: &str) -> Option<String> {
+    dt.parse::<DateTime<Utc>>().map(|dt| dt.to_rfc3339_opts(SecondsFormat::Secs, true)).ok()
+}
+
+pub fn utc_rfc2822(dt: &str) -> Option<String> {
+    dt.parse::<DateTime<Utc>>().map(|dt| dt.to_rfc2822()).ok()
+}
+
+pub fn utc_timestamp(dt: &str) -> Option<i64> {
+    dt.parse::<DateTime<Utc>>().map(|dt| dt.timestamp()).ok()
+}
+
+// Date format used in mbox "From " separator lines
+pub fn utc_asctime(dt: &str) -> Option<String> {
+    dt.parse::<DateTime<Utc>>().map(|dt| dt.format("%a %b %e %H:%M:%S %Y").to_string()).ok()
+}
+
+pub fn unix_now() -> i64 {
+    Utc::now().timestamp()
+}
+
+// Accepts durations like "30m", "2h", "7d" and "1w"
+pub fn parse_duration(arg: &str) -> Result<Duration, String> {
+    let arg = arg.trim();
+    let split = arg.find(|c: char| !c.is_ascii_digit()).unwrap_or(arg.len());
+    let (number, unit) = arg.split_at(split);
+    let number = number.parse::<i64>().map_err(|_| format!("Invalid duration \"{}\" (format: 30m, 2h, 7d)", arg))?;
+    match unit.trim() {
+        "s" => Ok(Duration::seconds(number)),
+        "m" | "min" => Ok(Duration::minutes(number)),
+        "h" => Ok(Duration::hours(number)),
+        "d" => Ok(Duration::days(number)),
+        "w" => Ok(Duration::weeks(number)),
+        _ => Err(format!("Invalid duration unit in \"{}\" (expected s, m, h, d or w)", arg)),
+    }
+}
+
+pub fn utc_offset_from_now(duration: Duration) -> String {
+    format!("{}", Utc::now() + duration)
+}
+
+// Format used for date-times in iCalendar files
+pub fn utc_ical(dt: &str) -> Option<String> {
+    dt.parse::<DateTime<Utc>>().map(|dt| dt.format("%Y%m%dT%H%M%SZ").to_string()).ok()
+}
+
+// Format used for query parameters in the Blackboard REST API
+pub fn utc_api(dt: &str) -> Option<String> {
+    dt.parse::<DateTime<Utc>>().map(|dt| dt.to_rfc3339_opts(SecondsFormat::Millis, true)).ok()
+}
+
+// Human readable distance to a point in time, like "in 2 days 4 h" or "3 h 20 min ago"
+pub fn relative_to_now(dt: &str) -> String {
+    let dt = match dt.parse::<DateTime<Utc>>() {
+        Ok(dt) => dt,
+        Err(_) => return String::from("<null>"),
+    };
+    let difference = dt - Utc::now();
+    let minutes = difference.num_minutes().abs();
+    let (days, hours, minutes) = (minutes / (24*60), minutes / 60 % 24, minutes % 60);
+    let distance = if days > 0 {
+        format!("{} day{} {} h", days, if days == 1 { "" } else { "s" }, hours)
+    } else if hours > 0 {
+        format!("{} h {} min", hours, minutes)
+    } else {
+        format!("{} min", minutes)
+    };
+    if difference < Duration::zero() {
+        format!("{} ago", distance)
+    } else {
+        format!("in {}", distance)
+    }
+}
+
+// Number of local calendar days from today until dt (negative if in the past)
+pub fn local_days_from_today(dt: &str) -> Option<i64> {
+    dt.parse::<DateTime<Local>>().map(|dt| (dt.date() - Local::today()).num_days()).ok()
+}
@@ -0,0 +1,218 @@
+pub mod watch_state;
+
+use std::path::{Path, PathBuf};
+use std::io::Write;
+use crate::bb_session::BBSession;
+use crate::bb_course::BBCourse;
+use crate::bb_course::bb_announcement::BBAnnouncement;
+use crate::bb_course::bb_manifest::BBManifestFile;
+use crate::content_store::ContentStore;
+use crate::utils::time_utils::{utc_now, utc_rfc3339};
+use crate::hooks::{Hook, run_command};
+use watch_state::WatchState;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchEventKind {
+    NewFile,
+    UpdatedFile,
+    RemovedFile,
+    NewAnnouncement,
+    NewDeadline,
+    ChangedDeadline,
+    NewGrade,
+    CourseSynced,
+}
+
+impl WatchEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WatchEventKind::NewFile => "new_file",
+            WatchEventKind::UpdatedFile => "updated_file",
+            WatchEventKind::RemovedFile => "removed_file",
+            WatchEventKind::NewAnnouncement => "new_announcement",
+            WatchEventKind::NewDeadline => "new_deadline",
+            WatchEventKind::ChangedDeadline => "changed_deadline",
+            WatchEventKind::NewGrade => "new_grade",
+            WatchEventKind::CourseSynced => "course_synced",
+        }
+    }
+}
+
+pub struct WatchEvent {
+    pub kind: WatchEventKind,
+    pub course_alias: String,
+    pub title: String,
+    pub content_id: String, // Content, announcement or gradebook column id
+    pub path: Option<PathBuf>,
+    pub mimetype: Option<String>,
+    pub due: Option<String>,
+    pub grade: Option<String>,
+    pub time: String,
+    pub baseline: bool, // Part of the first round a course is watched, which isn't notified
+}
+
+impl WatchEvent {
+    fn new(kind: WatchEventKind, course_alias: &str, title: &str, content_id: &str) -> WatchEvent {
+        WatchEvent {
+            kind,
+            course_alias: course_alias.to_string(),
+            title: title.to_string(),
+            content_id: content_id.to_string(),
+            path: None,
+            mimetype: None,
+            due: None,
+            grade: None,
+            time: utc_now(),
+            baseline: false,
+        }
+    }
+
+    pub fn to_json_line(&self) -> String {
+        json::JsonValue::from(self).dump()
+    }
+
+    // The environment variables hook commands are run with
+    pub fn env_vars(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("BBCM_EVENT", self.kind.as_str().to_string()),
+            ("BBCM_COURSE", self.course_alias.clone()),
+            ("BBCM_TITLE", self.title.clone()),
+            ("BBCM_CONTENT_ID", self.content_id.clone()),
+            ("BBCM_PATH", self.path.as_ref().map(|path| path.to_string_lossy().to_string()).unwrap_or_default()),
+            ("BBCM_MIMETYPE", self.mimetype.clone().unwrap_or_default()),
+        ]
+    }
+}
+
+impl std::convert::From<&WatchEvent> for json::JsonValue {
+    fn from(event: &WatchEvent) -> json::JsonValue {
+        let mut json_object = json::object!{
+            time: utc_rfc3339(&event.time).unwrap_or_else(|| event.time.clone()),
+            event: event.kind.as_str(),
+            course: event.course_alias.clone(),
+            title: event.title.clone(),
+            content_id: event.content_id.clone(),
+        };
+        if let Some(path) = &event.path {
+            json_object["path"] = path.to_string_lossy().to_string().into();
+        }
+        if let Some(mimetype) = &event.mimetype {
+            json_object["mimetype"] = mimetype.clone().into();
+        }
+        if let Some(due) = &event.due {
+            json_object["due"] = utc_rfc3339(due).unwrap_or_else(|| due.clone()).into();
+        }
+        if let Some(grade) = &event.grade {
+            json_object["grade"] = grade.clone().into();
+        }
+        if event.baseline {
+            json_object["baseline"] = true.into();
+        }
+        json_object
+    }
+}
+
+// One watch round for a course: syncs the content tree, announcements and gradebook, and reports what changed, followed by a course_synced event.
+// The first time a course is watched, announcements, deadlines and grades are only recorded, as they were already there.
+// Files downloaded in that round are still reported, so that hooks run for them, but they are marked as baseline and not notified.
+pub fn sync_course(course: &mut BBCourse, session: &BBSession, store: Option<&ContentStore>, work_dir: &Path, user_id: &str) -> Result<Vec<WatchEvent>, Box<dyn std::error::Error>> {
+    let mut state = WatchState::load(&work_dir.join("watch").join(format!("{}.json", course.id)))?;
+    let (_, _, mut events) = tree_events(course, session, store, false)?;
+    let announcement_events = announcement_events(course, session, work_dir)?;
+    let gradebook_events = gradebook_events(course, session, user_id, &mut state)?;
+    if state.is_new {
+        for event in &mut events {
+            event.baseline = true;
+        }
+    } else {
+        events.extend(announcement_events);
+        events.extend(gradebook_events);
+    }
+    state.save()?;
+    events.push(course_synced_event(course));
+    Ok(events)
+}
+
+// Downloads the content tree and returns the download size and the size of kept unchanged files, along with the new, changed and removed files.
+// Files count as changed when their content changed since they were last synced, as recorded in the sync manifest.
+pub fn tree_events(course: &mut BBCourse, session: &BBSession, store: Option<&ContentStore>, overwrite: bool) -> Result<(f64, f64, Vec<WatchEvent>), Box<dyn std::error::Error>> {
+    let previous = course.load_manifest()?.files;
+    let (download_size, saved_size) = course.download_course_content_tree(session, store, overwrite)?;
+    course.last_tree_download = utc_now();
+    let manifest = course.load_manifest()?;
+    let file_event = |kind: WatchEventKind, path: &Path, file: &BBManifestFile| {
+        let mut event = WatchEvent::new(kind, &course.alias, &path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default(), &file.content_id);
+        event.path = Some(manifest.absolute_path(path));
+        event.mimetype = Some(file.mimetype.clone());
+        event
+    };
+    let mut events: Vec<WatchEvent> = manifest.files.iter().filter_map(|(path, file)| {
+        let kind = match previous.get(path) {
+            None => WatchEventKind::NewFile,
+            Some(previous_file) if previous_file.synced != file.synced => WatchEventKind::UpdatedFile,
+            Some(_) => return None,
+        };
+        Some(file_event(kind, path, file))
+    }).collect();
+    events.extend(previous.iter()
+        .filter(|(path, _)| !manifest.files.contains_key(*path))
+        .map(|(path, file)| file_event(WatchEventKind::RemovedFile, path, file)));
+    Ok((download_size, saved_size, events))
+}
+
+pub fn course_synced_event(course: &BBCourse) -> WatchEvent {
+    let mut event = WatchEvent::new(WatchEventKind::CourseSynced, &course.alias, &course.info.name, &course.id);
+    event.path = Some(course.out_dir().to_path_buf());
+    event
+}
+
+pub fn announcement_event(course: &BBCourse, announcement: &BBAnnouncement) -> WatchEvent {
+    WatchEvent::new(WatchEventKind::NewAnnouncement, &course.alias, &announcement.title, &announcement.id)
+}
+
+// Announcements are new until they have been reported, by watch or by the commands that show them
+fn announcement_events(course: &BBCourse, session: &BBSession, work_dir: &Path) -> Result<Vec<WatchEvent>, Box<dyn std::error::Error>> {
+    let mut cache = course.update_announcement_cache(session, work_dir)?;
+    let ids: Vec<String> = cache.announcements().into_iter().map(|announcement| announcement.id.clone()).collect();
+    let new_announcements = cache.mark_reported(&ids);
+    cache.save()?;
+    Ok(new_announcements.iter().map(|announcement| announcement_event(course, announcement)).collect())
+}
+
+fn gradebook_events(course: &BBCourse, session: &BBSession, user_id: &str, state: &mut WatchState) -> Result<Vec<WatchEvent>, Box<dyn std::error::Error>> {
+    let mut events = Vec::new();
+    for (gbc, grade) in course.get_course_grades(session, user_id)? {
+        if gbc.due != "null" {
+            let kind = match state.deadlines.insert(gbc.id.clone(), gbc.due.clone()) {
+                None => Some(WatchEventKind::NewDeadline),
+                Some(due) if due != gbc.due => Some(WatchEventKind::ChangedDeadline),
+                Some(_) => None,
+            };
+            if let Some(kind) = kind {
+                let mut event = WatchEvent::new(kind, &course.alias, &gbc.name, &gbc.id);
+                event.due = Some(gbc.due.clone());
+                events.push(event);
+            }
+        }
+        if let Some(grade) = grade.map(|grade| grade.display(gbc.possible)).filter(|grade| grade != "-") {
+            if state.grades.insert(gbc.id.clone(), grade.clone()).as_ref() != Some(&grade) {
+                let mut event = WatchEvent::new(WatchEventKind::NewGrade, &course.alias, &gbc.name, &gbc.id);
+                event.grade = Some(grade);
+                events.push(event);
+            }
+        }
+    }
+    Ok(events)
+}
+
+// Events are written to stdout as JSON lines, and to each --hook command on stdin
+pub fn emit(event: &WatchEvent, hook_commands: &[String]) {
+    let line = event.to_json_line();
+    println!("{}", line);
+    std::io::stdout().flush().expect("Error writing to stdout");
+    for command in hook_commands {
+        if let Err(err) = run_command(command, &line, &event.env_vars(), Hook::DEFAULT_TIMEOUT) {
+            eprintln!("Hook \"{}\" failed: {}", command, err);
+        }
+    }
+}
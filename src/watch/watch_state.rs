@@ -0,0 +1,56 @@
+use std::path::{Path, PathBuf};
+use std::io::{Read, Write};
+use std::collections::HashMap;
+
+// Due dates and grades seen by the previous watch round for one course, keyed by gradebook column id.
+// New announcements are tracked in the announcement cache instead.
+pub struct WatchState {
+    path: PathBuf,
+    pub is_new: bool,
+    pub deadlines: HashMap<String, String>,
+    pub grades: HashMap<String, String>,
+}
+
+impl WatchState {
+    pub fn load(path: &Path) -> Result<WatchState, Box<dyn std::error::Error>> {
+        let mut deadlines = HashMap::new();
+        let mut grades = HashMap::new();
+        let is_new = !path.exists();
+        if !is_new {
+            let mut json_string = String::new();
+            std::fs::File::open(path)?.read_to_string(&mut json_string)?;
+            let parsed_json = json::parse(&json_string)?;
+            for (column_id, due) in parsed_json["deadlines"].entries() {
+                deadlines.insert(column_id.to_string(), due.to_string());
+            }
+            for (column_id, grade) in parsed_json["grades"].entries() {
+                grades.insert(column_id.to_string(), grade.to_string());
+            }
+        }
+        Ok(WatchState {
+            path: path.to_path_buf(),
+            is_new,
+            deadlines,
+            grades,
+        })
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut state = json::object!{
+            deadlines: json::JsonValue::new_object(),
+            grades: json::JsonValue::new_object(),
+        };
+        for (column_id, due) in &self.deadlines {
+            state["deadlines"][column_id.as_str()] = due.clone().into();
+        }
+        for (column_id, grade) in &self.grades {
+            state["grades"][column_id.as_str()] = grade.clone().into();
+        }
+        let mut state_file = std::fs::File::create(&self.path)?;
+        state_file.write_all(state.pretty(4).as_bytes())?;
+        Ok(())
+    }
+}